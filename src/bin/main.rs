@@ -1,51 +1,1140 @@
 use std::env;
+use std::fmt;
 use std::fs;
+use std::io;
+use std::io::{BufRead, Write};
+use std::process;
+use std::path::{Path, PathBuf};
+use std::collections::BTreeSet;
+use std::time::{Duration, Instant};
 
-use yoloxide::environment::{
-    Environment
-};
+use log::{Log, Metadata, Record, LevelFilter};
+
+use yoloxide::chip::Chip;
+use yoloxide::timeline::Timeline;
+use yoloxide::testing::TestSpec;
+use yoloxide::trace::Trace;
+use yoloxide::debugger::{Debugger, DebugEvent};
+use yoloxide::repl::Repl;
+use yoloxide::vm::Vm;
+use yoloxide::minifier;
+use yoloxide::optimizer;
+use yoloxide::linter;
+use yoloxide::inference;
+use yoloxide::formatter::{self, Formatter, FormatStyle, Spacing, KeywordCase, ParenPolicy};
+use yoloxide::environment::{ContextMap, Environment, ErrorMode};
 
 use yoloxide::tokenizer;
 use yoloxide::parser;
-use yoloxide::interpreter;
 
-use yoloxide::types::VecWindow;
+use yoloxide::types::{
+    ast::program::Program,
+    ChipKind,
+    ChipLimits,
+    Spanned,
+    Token,
+    TimelineError,
+    TokenWindow,
+    YoloxideError,
+};
+
+const USAGE: &str = "\
+Usage: yoloxide <command> <file> [options]
+
+Commands:
+    run <file>      Executes the script and prints its variables
+    check <file>    Parses the script and reports any errors
+    lint <file>     Warns about likely mistakes, like unused variables and lines that can never run
+    types <file>    Prints the type each variable can have at the start of each line
+    fmt <file>      Prints the script in the standard format, or the style given by the fmt options
+    minify <file>   Prints the script as short as it can be, and how long each line was before and after
+    optimize <file> Prints the script with constants folded and branches that never run removed
+    tokens <file>   Prints the tokens of the script
+    ast <file>      Prints the parsed program
+    repl            Evaluates yolol lines as they're typed, type ':help' once it's started for the commands
+    debug <file>    Steps through the script interactively, type 'help' once it's started for the commands
+    bench <file>    Times the script on the tree walking interpreter and the bytecode vm, and checks they agree
+    trace-diff <a> <b>  Compares two json traces and shows where they first differ
+    test [paths]    Runs the test specs given, or all the *.test.json specs in the given directories.
+                    Defaults to the current directory.
+
+Options:
+    --ticks <n>     run: number of lines to execute, defaults to one per line of the script
+                    bench: defaults to 100000
+    --var <name>    run: only print the given variable, can be repeated
+    --abort-line    run, debug, bench: runtime errors skip the rest of the line like in game, instead of stopping
+    --timeline <f>  run: set data fields from a timeline file and print the recorded fields after each tick
+    --trace <f>     run: write every statement executed, with the variables it read and wrote, to a file.
+                    Written as json if the file ends in .json, otherwise as text.
+    --chip <kind>   run, check, lint, types, debug, bench, minify: basic, advanced or professional, defaults to professional
+    --write         fmt: overwrite the file instead of printing it
+    --check         fmt: exit with an error if the file isn't already formatted, instead of printing it
+    --compact       fmt: only put spaces where they're needed
+    --upper         fmt: write keywords in upper case
+    --parens <p>    fmt: preserve keeps every parenthesis, minimal removes the ones that aren't needed
+    --json          tokens, ast, lint: print as json
+
+Set YOLOXIDE_LOG to a log level, like debug or trace, to print what the tokenizer and parser are doing.
+";
+
+// Options that take a value, all others are flags
+const VALUE_OPTIONS: &[&str] = &["--ticks", "--var", "--chip", "--timeline", "--trace", "--parens"];
+
+enum CliError
+{
+    Usage(String),
+    Io(String, io::Error),
+    Yoloxide(String, Box<YoloxideError>),
+    Timeline(String, TimelineError),
+    Json(serde_json::Error),
+    /// The formatted script didn't parse back to the same program
+    RoundTrip(String),
+    /// The command failed and has already reported why, like a failing test
+    Failed
+}
+
+impl fmt::Display for CliError
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+    {
+        match self
+        {
+            CliError::Usage(message) => write!(f, "{}\n\n{}", message, USAGE),
+            CliError::Io(path, error) => write!(f, "{}: {}", path, error),
+            CliError::Yoloxide(path, error) => write!(f, "{}:{}", path, error),
+            CliError::Timeline(path, error) => write!(f, "{}:{}", path, error),
+            CliError::Json(error) => write!(f, "failed to write json: {}", error),
+            CliError::RoundTrip(path) => write!(f, "{}: formatting changed the meaning of the script, this is a bug in the formatter", path),
+            CliError::Failed => Ok(())
+        }
+    }
+}
+
+impl From<serde_json::Error> for CliError
+{
+    fn from(error: serde_json::Error) -> Self
+    {
+        CliError::Json(error)
+    }
+}
+
+/// The command line split up into the command, its positional arguments and its options
+struct Args
+{
+    command: String,
+    positional: Vec<String>,
+    flags: Vec<String>,
+    options: Vec<(String, String)>
+}
+
+impl Args
+{
+    fn parse(input: Vec<String>) -> Result<Args, CliError>
+    {
+        let mut input = input.into_iter();
+
+        let command = match input.next()
+        {
+            Some(command) => command,
+            None => return Err(CliError::Usage(String::from("No command given")))
+        };
+
+        let mut args = Args {
+            command,
+            positional: Vec::new(),
+            flags: Vec::new(),
+            options: Vec::new()
+        };
+
+        while let Some(arg) = input.next()
+        {
+            if VALUE_OPTIONS.contains(&arg.as_str())
+            {
+                match input.next()
+                {
+                    Some(value) => args.options.push((arg, value)),
+                    None => return Err(CliError::Usage(format!("Option '{}' needs a value", arg)))
+                }
+            }
+            else if arg.starts_with("--")
+            {
+                args.flags.push(arg);
+            }
+            else
+            {
+                args.positional.push(arg);
+            }
+        }
+
+        Ok(args)
+    }
+
+    fn has_flag(&self, flag: &str) -> bool
+    {
+        self.flags.iter().any(|arg| arg == flag)
+    }
+
+    /// Returns every value given for the option, in order
+    fn option_values(&self, option: &str) -> Vec<&str>
+    {
+        self.options.iter()
+            .filter(|(name, _)| name == option)
+            .map(|(_, value)| value.as_str())
+            .collect()
+    }
+
+    /// Returns the last value given for the option
+    fn option(&self, option: &str) -> Option<&str>
+    {
+        self.option_values(option).pop()
+    }
+
+    /// Returns the path of the script to work on, which is the only positional argument
+    fn file(&self) -> Result<&str, CliError>
+    {
+        match self.positional.as_slice()
+        {
+            [file] => Ok(file),
+            [] => Err(CliError::Usage(format!("'{}' needs a file", self.command))),
+            _ => Err(CliError::Usage(format!("'{}' takes a single file", self.command)))
+        }
+    }
+
+    /// Returns the number given with --ticks, or the default
+    fn ticks(&self, default: u64) -> Result<u64, CliError>
+    {
+        match self.option("--ticks")
+        {
+            Some(ticks) => ticks.parse::<u64>()
+                .map_err(|_| CliError::Usage(format!("'{}' isn't a valid number of ticks", ticks))),
+            None => Ok(default)
+        }
+    }
+
+    fn limits(&self) -> Result<ChipLimits, CliError>
+    {
+        let kind = match self.option("--chip")
+        {
+            None | Some("professional") => ChipKind::Professional,
+            Some("advanced") => ChipKind::Advanced,
+            Some("basic") => ChipKind::Basic,
+            Some(other) => return Err(CliError::Usage(format!("Unknown chip kind '{}'", other)))
+        };
+
+        Ok(ChipLimits::for_chip(kind))
+    }
+
+    /// Errors if any flags were given that the command doesn't know about
+    fn check_flags(&self, known: &[&str]) -> Result<(), CliError>
+    {
+        let known_options = self.options.iter().map(|(name, _)| name);
+        for arg in self.flags.iter().chain(known_options)
+        {
+            if !known.contains(&arg.as_str())
+            {
+                return Err(CliError::Usage(format!("'{}' doesn't take the option '{}'", self.command, arg)));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Prints library log messages to stderr, so they never mix with a command's output
+struct StderrLogger;
+
+impl Log for StderrLogger
+{
+    fn enabled(&self, _metadata: &Metadata) -> bool
+    {
+        true
+    }
+
+    fn log(&self, record: &Record)
+    {
+        eprintln!("[{} {}] {}", record.level(), record.target(), record.args());
+    }
+
+    fn flush(&self) {}
+}
+
+static LOGGER: StderrLogger = StderrLogger;
+
+fn init_logging()
+{
+    let level = match env::var("YOLOXIDE_LOG").ok().and_then(|level| level.parse::<LevelFilter>().ok())
+    {
+        Some(level) => level,
+        None => return
+    };
+
+    if log::set_logger(&LOGGER).is_ok()
+    {
+        log::set_max_level(level);
+    }
+}
 
 fn main()
 {
-    let args: Vec<String> = env::args().collect();
+    init_logging();
+
+    let args: Vec<String> = env::args().skip(1).collect();
+
+    let result = Args::parse(args).and_then(|args| {
+        match args.command.as_str()
+        {
+            "run" => run(&args),
+            "check" => check(&args),
+            "fmt" => fmt(&args),
+            "minify" => minify(&args),
+            "optimize" => optimize(&args),
+            "lint" => lint(&args),
+            "types" => types(&args),
+            "tokens" => tokens(&args),
+            "ast" => ast(&args),
+            "test" => test(&args),
+            "trace-diff" => trace_diff(&args),
+            "debug" => debug(&args),
+            "repl" => repl(&args),
+            "bench" => bench(&args),
+            "help" | "--help" | "-h" => {
+                print!("{}", USAGE);
+                Ok(())
+            },
+            other => Err(CliError::Usage(format!("Unknown command '{}'", other)))
+        }
+    });
+
+    match result
+    {
+        Ok(()) => {},
+        Err(CliError::Failed) => process::exit(1),
+        Err(error) => {
+            eprintln!("{}", error);
+            process::exit(1);
+        }
+    }
+}
+
+fn read_source(path: &str) -> Result<String, CliError>
+{
+    fs::read_to_string(path).map_err(|error| CliError::Io(String::from(path), error))
+}
+
+// Timelines ending in .json are read as json, anything else as text
+fn read_timeline(path: &str) -> Result<Timeline, CliError>
+{
+    let source = read_source(path)?;
+
+    let timeline = if path.ends_with(".json")
+    {
+        Timeline::from_json(&source)
+    }
+    else
+    {
+        Timeline::from_text(&source)
+    };
+
+    timeline.map_err(|error| CliError::Timeline(String::from(path), error))
+}
+
+fn read_tokens(path: &str) -> Result<Vec<Spanned<Token>>, CliError>
+{
+    tokenizer::tokenize(read_source(path)?)
+        .map_err(|error| CliError::Yoloxide(String::from(path), Box::new(error.into())))
+}
+
+fn read_program(path: &str, limits: &ChipLimits) -> Result<Program, CliError>
+{
+    let mut window = TokenWindow::from(read_tokens(path)?);
+
+    parser::parse_program_with_limits(&mut window, limits)
+        .map_err(|error| CliError::Yoloxide(String::from(path), Box::new(error.into())))
+}
+
+fn run(args: &Args) -> Result<(), CliError>
+{
+    args.check_flags(&["--ticks", "--var", "--chip", "--abort-line", "--timeline", "--trace"])?;
+    let path = args.file()?;
+
+    let limits = args.limits()?;
+    let program = read_program(path, &limits)?;
+
+    let ticks = args.ticks(program.0.len() as u64)?;
+
+    let mut chip = Chip::new(path, program);
+    chip.env.limits = limits;
+
+    if args.has_flag("--abort-line")
+    {
+        chip.env.error_mode = ErrorMode::AbortLine;
+    }
+
+    if args.option("--trace").is_some()
+    {
+        chip.env.start_trace();
+    }
+
+    let result = match args.option("--timeline")
+    {
+        Some(timeline_path) => {
+            let timeline = read_timeline(timeline_path)?;
+            timeline.run_chip(&mut chip, ticks).map(|records| {
+                for record in records
+                {
+                    println!("{}", record);
+                }
+            })
+        },
+        None => chip.run_ticks(ticks)
+    };
+
+    for error in &chip.env.runtime_errors
+    {
+        eprintln!("{}:{}", path, error.error);
+    }
+
+    if let (Some(trace_path), Some(trace)) = (args.option("--trace"), chip.env.take_trace())
+    {
+        write_trace(trace_path, &trace)?;
+    }
+
+    let vars = args.option_values("--var");
+    if vars.is_empty() && args.option("--timeline").is_none()
+    {
+        println!("{}", chip.env);
+    }
+    else
+    {
+        for var in vars
+        {
+            println!("{} = {}", var, chip.env.get_val(var));
+        }
+    }
+
+    result.map_err(|error| CliError::Yoloxide(String::from(path), Box::new(error.into())))
+}
+
+fn check(args: &Args) -> Result<(), CliError>
+{
+    args.check_flags(&["--chip"])?;
+    let path = args.file()?;
+
+    read_program(path, &args.limits()?)?;
+    Ok(())
+}
+
+fn fmt(args: &Args) -> Result<(), CliError>
+{
+    args.check_flags(&["--write", "--check", "--compact", "--upper", "--parens"])?;
+    let path = args.file()?;
+
+    let parens = match args.option("--parens")
+    {
+        None | Some("preserve") => ParenPolicy::Preserve,
+        Some("minimal") => ParenPolicy::Minimal,
+        Some(other) => return Err(CliError::Usage(format!("Unknown paren policy '{}'", other)))
+    };
+
+    let style = FormatStyle {
+        spacing: if args.has_flag("--compact") { Spacing::Compact } else { Spacing::Normal },
+        keyword_case: if args.has_flag("--upper") { KeywordCase::Upper } else { KeywordCase::Lower },
+        parens
+    };
+
+    let source = read_source(path)?;
+    let program = read_program(path, &ChipLimits::unlimited())?;
+    let formatted = Formatter::new(style).format_program(&program);
+
+    // Formatting should never change what the script does, so make sure it parses back the same
+    let reparsed = tokenizer::tokenize(formatted.clone()).ok()
+        .and_then(|tokens| parser::parse_program(&mut TokenWindow::from(tokens)).ok());
+
+    // Minimal parens drop groups from the tree, so formatting again has to give the same text instead
+    let round_trips = match (style.parens, &reparsed)
+    {
+        (ParenPolicy::Preserve, Some(reparsed)) => formatter::same_code(&program, reparsed),
+        (ParenPolicy::Minimal, Some(reparsed)) => Formatter::new(style).format_program(reparsed) == formatted,
+        (_, None) => false
+    };
+
+    if !round_trips
+    {
+        return Err(CliError::RoundTrip(String::from(path)));
+    }
+
+    if args.has_flag("--check")
+    {
+        if source.replace("\r\n", "\n") != formatted
+        {
+            println!("{} isn't formatted", path);
+            return Err(CliError::Failed);
+        }
+
+        Ok(())
+    }
+    else if args.has_flag("--write")
+    {
+        fs::write(path, formatted).map_err(|error| CliError::Io(String::from(path), error))
+    }
+    else
+    {
+        print!("{}", formatted);
+        Ok(())
+    }
+}
+
+// Exits with an error if there are any lints, so it can fail a CI build
+fn lint(args: &Args) -> Result<(), CliError>
+{
+    args.check_flags(&["--chip", "--json"])?;
+    let path = args.file()?;
 
-    // Take the first argument as a file path and read it for yolol code
-    let yolol_code = fs::read_to_string(&args[1]).unwrap();
+    let source = read_source(path)?;
+    let lints = linter::lint(&read_program(path, &ChipLimits::unlimited())?, &source, &args.limits()?);
 
-    println!("Original code:");
-    println!("{}", yolol_code);
+    if args.has_flag("--json")
+    {
+        let mut output = Vec::new();
+        for lint in &lints
+        {
+            let mut value = serde_json::to_value(lint)?;
+            value["message"] = serde_json::Value::from(lint.kind.to_string());
+            output.push(value);
+        }
 
-    let tokens = tokenizer::tokenize(yolol_code).expect("Tokenizer failure!");
-    println!("Tokens:");
-    println!("{:?}", tokens);
+        println!("{}", serde_json::to_string_pretty(&output)?);
+    }
+    else
+    {
+        for lint in &lints
+        {
+            println!("{}:{}", path, lint);
+        }
+    }
 
-    let mut token_window = VecWindow::from(tokens);
-    let program = parser::parse_program(&mut token_window).expect("Parser failure!");
+    if !lints.is_empty()
+    {
+        return Err(CliError::Failed);
+    }
 
-    println!("AST:");
-    for line in &program.0
+    Ok(())
+}
+
+fn types(args: &Args) -> Result<(), CliError>
+{
+    args.check_flags(&["--chip"])?;
+    let path = args.file()?;
+    let limits = args.limits()?;
+
+    let program = read_program(path, &ChipLimits::unlimited())?;
+    let types = inference::infer(&program, &limits);
+
+    for line in 1..=program.0.len()
     {
-        println!("{:?}", line);
+        match types.line_entry(line)
+        {
+            Some(env) => {
+                let variables: Vec<String> = env.variables().iter()
+                    .map(|(name, value_type)| format!("{}: {}", types.name(name), value_type))
+                    .collect();
+
+                println!("line {}: {}", line, variables.join(", "));
+            },
+            None => println!("line {}: never runs", line)
+        }
     }
 
-    let mut test_env = Environment::new("Test Env");
+    Ok(())
+}
+
+fn optimize(args: &Args) -> Result<(), CliError>
+{
+    args.check_flags(&[])?;
+    let path = args.file()?;
+
+    let optimized = optimizer::optimize(&read_program(path, &ChipLimits::unlimited())?);
+    print!("{}", optimizer::format_optimized(&optimized));
+
+    Ok(())
+}
+
+// The minified script goes to stdout and the report to stderr, so the script can be piped somewhere
+fn minify(args: &Args) -> Result<(), CliError>
+{
+    args.check_flags(&["--chip"])?;
+    let path = args.file()?;
+    let limits = args.limits()?;
+
+    let source = read_source(path)?;
+    let minified = minifier::minify(&read_program(path, &ChipLimits::unlimited())?, &source);
+    print!("{}", minified.source);
 
-    println!("Re-codified AST:");
-    for line in &program.0
+    for (index, size) in minified.sizes.iter().enumerate()
     {
-        println!("{}", line);
-        let eval_output = interpreter::evaluate_line(&mut test_env, &line);
+        let warning = if size.after > limits.max_line_chars { ", too long for the chip" } else { "" };
+        eprintln!("line {}: {} -> {} chars{}", index + 1, size.before, size.after, warning);
+    }
+
+    let before: usize = minified.sizes.iter().map(|size| size.before).sum();
+    let after: usize = minified.sizes.iter().map(|size| size.after).sum();
+    eprintln!("total: {} -> {} chars", before, after);
 
-        eval_output.unwrap_or_else(|error| {
+    Ok(())
+}
+
+fn tokens(args: &Args) -> Result<(), CliError>
+{
+    args.check_flags(&["--json"])?;
+    let tokens = read_tokens(args.file()?)?;
+
+    if args.has_flag("--json")
+    {
+        println!("{}", serde_json::to_string_pretty(&tokens)?);
+    }
+    else
+    {
+        for token in tokens
+        {
+            println!("{}: {:?}", token.span, token.node);
+        }
+    }
+
+    Ok(())
+}
+
+fn ast(args: &Args) -> Result<(), CliError>
+{
+    args.check_flags(&["--json"])?;
+    let program = read_program(args.file()?, &ChipLimits::unlimited())?;
+
+    if args.has_flag("--json")
+    {
+        println!("{}", serde_json::to_string_pretty(&program)?);
+    }
+    else
+    {
+        for (index, line) in program.0.iter().enumerate()
+        {
+            println!("Line {}:", index + 1);
+            for statement in &line.0
+            {
+                println!("    {}: {:?}", statement.span, statement.node);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+const SPEC_EXTENSION: &str = ".test.json";
+
+// Expands the paths given into spec files, looking inside directories for anything ending in the spec extension
+fn find_specs(paths: &[String]) -> Result<Vec<PathBuf>, CliError>
+{
+    let mut specs = Vec::new();
+
+    for path in paths
+    {
+        let path = PathBuf::from(path);
+        if !path.is_dir()
+        {
+            specs.push(path);
+            continue;
+        }
+
+        let entries = fs::read_dir(&path)
+            .map_err(|error| CliError::Io(path.display().to_string(), error))?;
+
+        let mut found: Vec<PathBuf> = entries
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|entry| entry.to_string_lossy().ends_with(SPEC_EXTENSION))
+            .collect();
+
+        found.sort();
+        specs.extend(found);
+    }
+
+    Ok(specs)
+}
+
+fn read_spec(path: &Path) -> Result<TestSpec, String>
+{
+    let source = fs::read_to_string(path).map_err(|error| error.to_string())?;
+    let mut spec = TestSpec::from_json(&source).map_err(|error| error.to_string())?;
+
+    if spec.name.is_empty()
+    {
+        let file_name = path.file_name().map(|name| name.to_string_lossy()).unwrap_or_default();
+        spec.name = file_name.trim_end_matches(SPEC_EXTENSION).to_string();
+    }
+
+    Ok(spec)
+}
+
+fn test(args: &Args) -> Result<(), CliError>
+{
+    args.check_flags(&[])?;
+
+    let paths = match args.positional.as_slice()
+    {
+        [] => vec![String::from(".")],
+        paths => paths.to_vec()
+    };
+
+    let mut passed = 0;
+    let mut failed = 0;
+
+    for path in find_specs(&paths)?
+    {
+        let spec = match read_spec(&path)
+        {
+            Ok(spec) => spec,
+            Err(error) => {
+                println!("FAIL {}\n    {}", path.display(), error);
+                failed += 1;
+                continue;
+            }
+        };
+
+        let base_dir = path.parent().unwrap_or_else(|| Path::new("."));
+        let report = spec.run(base_dir);
+
+        println!("{}", report);
+        if report.passed()
+        {
+            passed += 1;
+        }
+        else
+        {
+            failed += 1;
+        }
+    }
+
+    println!("\n{} passed, {} failed", passed, failed);
+
+    if failed > 0
+    {
+        // The reports already say what went wrong, so there's nothing more to print
+        return Err(CliError::Failed);
+    }
+
+    Ok(())
+}
+
+fn write_trace(path: &str, trace: &Trace) -> Result<(), CliError>
+{
+    let output = if path.ends_with(".json")
+    {
+        serde_json::to_string_pretty(trace)?
+    }
+    else
+    {
+        trace.entries.iter()
+            .map(|entry| format!("{}\n", entry))
+            .collect()
+    };
+
+    fs::write(path, output).map_err(|error| CliError::Io(String::from(path), error))
+}
+
+fn read_trace(path: &str) -> Result<Trace, CliError>
+{
+    Trace::from_json(&read_source(path)?)
+        .map_err(|error| CliError::Usage(format!("{}: not a json trace: {}", path, error)))
+}
+
+fn trace_diff(args: &Args) -> Result<(), CliError>
+{
+    args.check_flags(&[])?;
+
+    let (left, right) = match args.positional.as_slice()
+    {
+        [left, right] => (read_trace(left)?, read_trace(right)?),
+        _ => return Err(CliError::Usage(String::from("'trace-diff' takes two trace files")))
+    };
+
+    match left.diff(&right)
+    {
+        Some(divergence) => {
+            println!("{}", divergence);
+            Err(CliError::Failed)
+        },
+        None => {
+            println!("traces are the same");
+            Ok(())
+        }
+    }
+}
+
+const DEBUG_HELP: &str = "\
+Commands:
+    step, s             Execute the next statement
+    next, n             Execute the rest of the current line
+    continue, c [n]     Run until a breakpoint or watch stops it, or for at most n lines
+    break, b <line>     Add or remove a breakpoint on a line
+    watch, w <name>     Add or remove a watch on a variable
+    print, p <name>     Print a variable
+    eval, e <yolol>     Execute yolol statements, like 'e :door=1'. Only a goto moves the chip
+    vars                Print every variable
+    list, l             Print the program, marking the next statement and breakpoints
+    help, h             Print this message
+    quit, q             Exit the debugger
+";
+
+// How many lines 'continue' runs for, if it isn't given a limit
+const DEFAULT_CONTINUE_TICKS: u64 = 100_000;
+
+fn debug(args: &Args) -> Result<(), CliError>
+{
+    args.check_flags(&["--chip", "--abort-line"])?;
+    let path = args.file()?;
+
+    let limits = args.limits()?;
+    let mut chip = Chip::new(path, read_program(path, &limits)?);
+    chip.env.limits = limits;
+
+    if args.has_flag("--abort-line")
+    {
+        chip.env.error_mode = ErrorMode::AbortLine;
+    }
+
+    let mut debugger = Debugger::new(chip);
+    print_position(&debugger);
+
+    let stdin = io::stdin();
+    let mut lines = stdin.lock().lines();
+
+    loop
+    {
+        let line = match prompt("(debug) ", &mut lines)?
+        {
+            Some(line) => line,
+            None => return Ok(())
+        };
+
+        let (command, argument) = match line.trim().split_once(char::is_whitespace)
+        {
+            Some((command, argument)) => (command, argument.trim()),
+            None => (line.trim(), "")
+        };
+
+        match command
+        {
+            "" => {},
+            "step" | "s" => {
+                let event = debugger.step_statement();
+                print_event(&debugger, event);
+            },
+            "next" | "n" => {
+                let event = debugger.step_line();
+                print_event(&debugger, event);
+            },
+            "continue" | "c" => {
+                let ticks = if argument.is_empty()
+                {
+                    Some(DEFAULT_CONTINUE_TICKS)
+                }
+                else
+                {
+                    argument.parse::<u64>().ok()
+                };
+
+                match ticks
+                {
+                    Some(ticks) => {
+                        let event = debugger.resume(ticks);
+                        print_event(&debugger, event);
+                    },
+                    None => println!("'{}' isn't a number of lines", argument)
+                }
+            },
+            "break" | "b" => match argument.parse::<i64>()
+            {
+                Ok(line) if debugger.remove_breakpoint(line) => println!("removed breakpoint on line {}", line),
+                Ok(line) => {
+                    debugger.add_breakpoint(line);
+                    println!("added breakpoint on line {}", line);
+                },
+                Err(_) => println!("'{}' isn't a line number", argument)
+            },
+            "watch" | "w" if !argument.is_empty() => {
+                if debugger.remove_watch(argument)
+                {
+                    println!("removed watch on {}", argument);
+                }
+                else
+                {
+                    debugger.add_watch(argument);
+                    println!("watching {}", argument);
+                }
+            },
+            "print" | "p" if !argument.is_empty() => println!("{} = {}", argument, debugger.get(argument)),
+            "eval" | "e" => {
+                let position = debugger.chip.position();
+
+                match debugger.eval(argument)
+                {
+                    Ok(()) if debugger.chip.position() != position => print_position(&debugger),
+                    Ok(()) => {},
+                    Err(error) => println!("error: {}", error)
+                }
+            },
+            "vars" => println!("{}", debugger.chip.env),
+            "list" | "l" => print_program(&debugger),
+            "help" | "h" => print!("{}", DEBUG_HELP),
+            "quit" | "q" => return Ok(()),
+            _ => println!("unknown command '{}', type 'help' for the commands", line.trim())
+        }
+    }
+}
+
+// Prints the prompt and reads the next line of input, None once it runs out
+fn prompt(text: &str, lines: &mut impl Iterator<Item = io::Result<String>>) -> Result<Option<String>, CliError>
+{
+    print!("{}", text);
+    io::stdout().flush().map_err(|error| CliError::Io(String::from("stdout"), error))?;
+
+    match lines.next()
+    {
+        Some(line) => line.map(Some).map_err(|error| CliError::Io(String::from("stdin"), error)),
+        None => Ok(None)
+    }
+}
+
+fn print_event(debugger: &Debugger, event: DebugEvent)
+{
+    if event != DebugEvent::Stepped
+    {
+        println!("{}", event);
+    }
+
+    print_position(debugger);
+}
+
+fn print_position(debugger: &Debugger)
+{
+    let (line, statement) = debugger.chip.position();
+
+    match debugger.next_statement()
+    {
+        Some(next) => println!("-> line {}, statement {}: {}", line, statement + 1, next),
+        None => println!("-> line {} (empty)", line)
+    }
+}
+
+fn print_program(debugger: &Debugger)
+{
+    let (current_line, _) = debugger.chip.position();
+
+    for (index, line) in debugger.chip.program.0.iter().enumerate()
+    {
+        let number = index as i64 + 1;
+
+        let current = if number == current_line { "->" } else { "  " };
+        let breakpoint = if debugger.breakpoints().contains(&number) { "*" } else { " " };
+
+        println!("{}{}{:>3} {}", current, breakpoint, number, line);
+    }
+}
+
+const REPL_HELP: &str = "\
+Type a line of yolol to run it, the variables it changed are printed afterwards.
+
+Commands:
+    :vars           Print every variable
+    :reset          Throw away every variable
+    :load <file>    Load an environment saved as json, or run each line of a yolol script once
+    :save <file>    Save the environment as json
+    :help           Print this message
+    :quit           Exit the repl
+";
+
+fn repl(args: &Args) -> Result<(), CliError>
+{
+    args.check_flags(&[])?;
+
+    let mut repl = Repl::new("repl");
+
+    let stdin = io::stdin();
+    let mut lines = stdin.lock().lines();
+
+    loop
+    {
+        let line = match prompt("> ", &mut lines)?
+        {
+            Some(line) => line,
+            None => return Ok(())
+        };
+
+        let line = line.trim();
+        let (command, argument) = match line.split_once(char::is_whitespace)
+        {
+            Some((command, argument)) => (command, argument.trim()),
+            None => (line, "")
+        };
+
+        let result = match command
+        {
+            "" => Ok(()),
+            ":vars" => {
+                for (name, value) in repl.variables()
+                {
+                    println!("{} = {}", name, value);
+                }
+
+                Ok(())
+            },
+            ":reset" => {
+                repl.reset();
+                Ok(())
+            },
+            ":load" if !argument.is_empty() => repl_load(&mut repl, argument),
+            ":save" if !argument.is_empty() => repl_save(&repl, argument),
+            ":help" => {
+                print!("{}", REPL_HELP);
+                Ok(())
+            },
+            ":quit" => return Ok(()),
+            // Anything else is yolol, including lines starting with a data field like `:door = 1`
+            _ => {
+                repl_eval(&mut repl, line);
+                Ok(())
+            }
+        };
+
+        // Nothing in the repl is worth exiting over
+        if let Err(error) = result
+        {
             println!("{}", error);
-        });
+        }
+    }
+}
+
+fn repl_eval(repl: &mut Repl, line: &str)
+{
+    let output = repl.eval(line);
+
+    for change in output.changes
+    {
+        println!("{} = {} -> {}", change.name, change.old, change.new);
+    }
+
+    if let Some(error) = output.error
+    {
+        println!("error: {}", error);
     }
+}
+
+fn repl_load(repl: &mut Repl, path: &str) -> Result<(), String>
+{
+    let source = read_source(path).map_err(|error| error.to_string())?;
+
+    if path.ends_with(".json")
+    {
+        repl.env = serde_json::from_str::<Environment>(&source)
+            .map_err(|error| format!("{}: not a json environment: {}", path, error))?;
 
-    println!("\n{}", test_env);
+        return Ok(());
+    }
+
+    for line in source.lines()
+    {
+        repl_eval(repl, line);
+    }
+
+    Ok(())
+}
+
+fn repl_save(repl: &Repl, path: &str) -> Result<(), String>
+{
+    let json = serde_json::to_string_pretty(&repl.env).map_err(|error| error.to_string())?;
+    fs::write(path, json).map_err(|error| format!("{}: {}", path, error))
+}
+
+const DEFAULT_BENCH_TICKS: u64 = 100_000;
+
+fn bench(args: &Args) -> Result<(), CliError>
+{
+    args.check_flags(&["--ticks", "--chip", "--abort-line"])?;
+    let path = args.file()?;
+
+    let limits = args.limits()?;
+    let program = read_program(path, &limits)?;
+    let ticks = args.ticks(DEFAULT_BENCH_TICKS)?;
+
+    let mut env = Environment::new(path);
+    env.limits = limits;
+
+    if args.has_flag("--abort-line")
+    {
+        env.error_mode = ErrorMode::AbortLine;
+    }
+
+    let mut chip = Chip::with_environment(program.clone(), env.clone());
+    let start = Instant::now();
+    let chip_result = chip.run_ticks(ticks);
+    let chip_time = start.elapsed();
+
+    let mut vm = Vm::new(&program, env);
+    let start = Instant::now();
+    let vm_result = vm.run_ticks(ticks);
+    let vm_time = start.elapsed();
+
+    print_bench("tree walker", ticks, chip_time);
+    print_bench("bytecode vm", ticks, vm_time);
+    println!("speedup: {:.2}x", chip_time.as_secs_f64() / vm_time.as_secs_f64());
+
+    let vm_env = vm.into_env();
+    let mut mismatches = Vec::new();
+
+    if chip_result != vm_result
+    {
+        mismatches.push(format!("result: {:?} != {:?}", chip_result, vm_result));
+    }
+
+    if chip.env.next_line != vm_env.next_line
+    {
+        mismatches.push(format!("next line: {} != {}", chip.env.next_line, vm_env.next_line));
+    }
+
+    if chip.env.runtime_errors.len() != vm_env.runtime_errors.len()
+    {
+        mismatches.push(format!("runtime errors: {} != {}", chip.env.runtime_errors.len(), vm_env.runtime_errors.len()));
+    }
+
+    let names: BTreeSet<&str> = chip.env.local_context()
+        .chain(chip.env.global_context().iter())
+        .chain(vm_env.local_context())
+        .chain(vm_env.global_context().iter())
+        .map(|(name, _)| name)
+        .collect();
+
+    for name in names
+    {
+        let (expected, actual) = (chip.env.get_val(name), vm_env.get_val(name));
+        if expected != actual
+        {
+            mismatches.push(format!("{}: {} != {}", name, expected, actual));
+        }
+    }
+
+    if mismatches.is_empty()
+    {
+        println!("results match");
+        return Ok(());
+    }
+
+    println!("results differ, tree walker != bytecode vm:");
+    for mismatch in mismatches
+    {
+        println!("    {}", mismatch);
+    }
+
+    Err(CliError::Failed)
+}
+
+fn print_bench(name: &str, ticks: u64, time: Duration)
+{
+    println!("{}: {} lines in {:.3}s, {:.0} lines/s", name, ticks, time.as_secs_f64(), ticks as f64 / time.as_secs_f64());
 }
@@ -0,0 +1,302 @@
+use std::fmt;
+use std::collections::HashMap;
+
+use crate::types::ast::{
+    statement::Statement as Stat,
+    expression::Expression as Expr,
+    operators::Operator as Op,
+    value::Value,
+    value::LiteralValue,
+    program::Program,
+    line::Line
+};
+
+use crate::types::{EvaluationErrorKind, Span, Spanned};
+
+use crate::environment::normalize_ident;
+
+/// A single operation of the stack machine in `vm`
+#[derive(Debug, Clone, PartialEq)]
+pub enum Instruction
+{
+    /// Marks the start of a statement, so errors can be pinned to it
+    Statement(usize),
+
+    Push(LiteralValue),
+    Pop,
+    /// Pushes the value of the variable in the slot
+    Load(usize),
+    /// Pops a value into the variable in the slot
+    Store(usize),
+
+    /// Pops the right then the left value and pushes the result
+    Binary(Op),
+    Unary(Op),
+    /// Pops the value of a compound assignment then the variable's current value, and pushes what to store
+    Assign(Op),
+    /// Increments or decrements the variable in the slot, pushing the value the expression evaluates to
+    Step(Op, usize),
+
+    /// Pops the condition of an if, jumping to the instruction at the index if it's false
+    JumpIfFalse(usize),
+    Jump(usize),
+    /// Pops the line to jump to, ending the line
+    Goto,
+
+    /// Errors with a problem the compiler found, once execution reaches it
+    Fail(Box<EvaluationErrorKind>)
+}
+
+impl fmt::Display for Instruction
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+    {
+        match self
+        {
+            Instruction::Statement(index) => write!(f, "statement {}", index),
+            Instruction::Push(value) => write!(f, "push {}", value),
+            Instruction::Pop => write!(f, "pop"),
+            Instruction::Load(slot) => write!(f, "load {}", slot),
+            Instruction::Store(slot) => write!(f, "store {}", slot),
+            Instruction::Binary(op) => write!(f, "binary {}", op),
+            Instruction::Unary(op) => write!(f, "unary {}", op),
+            Instruction::Assign(op) => write!(f, "assign {}", op),
+            Instruction::Step(op, slot) => write!(f, "step {} {}", op, slot),
+            Instruction::JumpIfFalse(target) => write!(f, "jump_if_false {}", target),
+            Instruction::Jump(target) => write!(f, "jump {}", target),
+            Instruction::Goto => write!(f, "goto"),
+            Instruction::Fail(kind) => write!(f, "fail {:?}", kind),
+        }
+    }
+}
+
+/// The instructions for one line of a program
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CompiledLine
+{
+    pub code: Vec<Instruction>,
+    /// The span of the code each instruction was compiled from, by index, so errors point where the interpreter's do
+    pub spans: Vec<Span>
+}
+
+/// A program compiled for the stack machine, with every variable resolved to a slot
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Bytecode
+{
+    pub lines: Vec<CompiledLine>,
+    /// The name of the variable in each slot, spelled as the program first used it
+    pub names: Vec<String>
+}
+
+impl Bytecode
+{
+    pub fn compile(program: &Program) -> Bytecode
+    {
+        let mut compiler = Compiler::default();
+
+        let lines = program.0.iter()
+            .map(|line| compiler.compile_line(line))
+            .collect();
+
+        Bytecode {
+            lines,
+            names: compiler.names
+        }
+    }
+
+    /// Returns the slot a variable was given, if the program uses it
+    pub fn slot(&self, name: &str) -> Option<usize>
+    {
+        let name = normalize_ident(name);
+        self.names.iter().position(|slot_name| normalize_ident(slot_name) == name)
+    }
+}
+
+impl fmt::Display for Bytecode
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+    {
+        for (slot, name) in self.names.iter().enumerate()
+        {
+            writeln!(f, "slot {}: {}", slot, name)?;
+        }
+
+        for (index, line) in self.lines.iter().enumerate()
+        {
+            writeln!(f, "line {}:", index + 1)?;
+            for (pc, instruction) in line.code.iter().enumerate()
+            {
+                writeln!(f, "    {:>4} {}", pc, instruction)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Default)]
+struct Compiler
+{
+    names: Vec<String>,
+    slots: HashMap<String, usize>,
+
+    // The line currently being compiled
+    code: Vec<Instruction>,
+    spans: Vec<Span>
+}
+
+impl Compiler
+{
+    fn slot(&mut self, name: &str) -> usize
+    {
+        let key = normalize_ident(name);
+        if let Some(&slot) = self.slots.get(key.as_ref())
+        {
+            return slot;
+        }
+
+        let slot = self.names.len();
+        self.names.push(String::from(name));
+        self.slots.insert(key.into_owned(), slot);
+
+        slot
+    }
+
+    fn emit(&mut self, instruction: Instruction, span: Span)
+    {
+        self.code.push(instruction);
+        self.spans.push(span);
+    }
+
+    fn compile_line(&mut self, line: &Line) -> CompiledLine
+    {
+        for (index, statement) in line.0.iter().enumerate()
+        {
+            self.emit(Instruction::Statement(index), statement.span);
+            self.compile_statement(statement);
+        }
+
+        CompiledLine {
+            code: std::mem::take(&mut self.code),
+            spans: std::mem::take(&mut self.spans)
+        }
+    }
+
+    fn compile_statement(&mut self, statement: &Spanned<Stat>)
+    {
+        let span = statement.span;
+
+        match &statement.node
+        {
+            Stat::Comment(_) => {},
+            Stat::If(cond, body, else_body) => {
+                self.compile_expression(cond);
+
+                let jump_to_else = self.code.len();
+                self.emit(Instruction::JumpIfFalse(0), span);
+
+                for statement in body
+                {
+                    self.compile_statement(statement);
+                }
+
+                match else_body
+                {
+                    Some(else_body) => {
+                        let jump_to_end = self.code.len();
+                        self.emit(Instruction::Jump(0), span);
+
+                        self.code[jump_to_else] = Instruction::JumpIfFalse(self.code.len());
+                        for statement in else_body
+                        {
+                            self.compile_statement(statement);
+                        }
+
+                        self.code[jump_to_end] = Instruction::Jump(self.code.len());
+                    },
+                    None => self.code[jump_to_else] = Instruction::JumpIfFalse(self.code.len())
+                }
+            },
+            Stat::Goto(target) => {
+                self.compile_expression(target);
+                self.emit(Instruction::Goto, span);
+            },
+            Stat::Assignment(ident, op, expr) => {
+                let ident = match ident
+                {
+                    Value::LocalVar(ident) |
+                    Value::DataField(ident) => ident,
+
+                    other => return self.fail(EvaluationErrorKind::InvalidAssignmentTarget(other.to_string()), span)
+                };
+
+                let slot = self.slot(&ident.name);
+                if let Op::Assign = op
+                {
+                    self.compile_expression(expr);
+                }
+                else
+                {
+                    self.emit(Instruction::Load(slot), span);
+                    self.compile_expression(expr);
+                    self.emit(Instruction::Assign(*op), span);
+                }
+
+                self.emit(Instruction::Store(slot), span);
+            },
+            Stat::Expression(expr) => {
+                self.compile_expression(expr);
+                self.emit(Instruction::Pop, span);
+            }
+        }
+    }
+
+    fn compile_expression(&mut self, expr: &Spanned<Expr>)
+    {
+        let span = expr.span;
+
+        match &expr.node
+        {
+            Expr::BinaryOp(op, left, right) => {
+                self.compile_expression(left);
+                self.compile_expression(right);
+                self.emit(Instruction::Binary(*op), span);
+            },
+            Expr::UnaryOp(op @ (Op::PreInc | Op::PostInc | Op::PreDec | Op::PostDec), target) => {
+                match &target.node
+                {
+                    Expr::Value(Value::LocalVar(ident)) |
+                    Expr::Value(Value::DataField(ident)) => {
+                        let slot = self.slot(&ident.name);
+                        self.emit(Instruction::Step(*op, slot), span);
+                    },
+                    other => self.fail(EvaluationErrorKind::InvalidAssignmentTarget(other.to_string()), span)
+                }
+            },
+            Expr::UnaryOp(op, target) => {
+                self.compile_expression(target);
+                self.emit(Instruction::Unary(*op), span);
+            },
+            Expr::Value(value) => self.compile_value(value, span)
+        }
+    }
+
+    fn compile_value(&mut self, value: &Value, span: Span)
+    {
+        let instruction = match value
+        {
+            Value::Group(expr) => return self.compile_expression(expr),
+            Value::LocalVar(ident) |
+            Value::DataField(ident) => Instruction::Load(self.slot(&ident.name)),
+            Value::NumberVal(number) => Instruction::Push(LiteralValue::NumberVal(*number)),
+            Value::StringVal(string) => Instruction::Push(LiteralValue::StringVal(string.clone())),
+        };
+
+        self.emit(instruction, span);
+    }
+
+    fn fail(&mut self, kind: EvaluationErrorKind, span: Span)
+    {
+        self.emit(Instruction::Fail(Box::new(kind)), span);
+    }
+}
@@ -0,0 +1,294 @@
+use crate::types::ast::program::Program;
+
+use crate::types::EvaluationError;
+use crate::types::YoloxideError;
+use crate::types::TokenWindow;
+use crate::types::ChipLimits;
+
+use crate::environment::Environment;
+use crate::device::{Devices, Snapshot};
+use crate::tokenizer;
+use crate::parser;
+use crate::interpreter;
+
+/// A single yolol chip: a parsed program and the environment it runs in.
+#[derive(Debug, Clone)]
+pub struct Chip
+{
+    pub program: Program,
+    pub env: Environment,
+    /// Simulated hardware on the chip's data fields
+    pub devices: Devices,
+
+    ticks: u64,
+    // Where the chip is part way through a line, when it's being stepped a statement at a time
+    line_state: Option<LineState>
+}
+
+#[derive(Debug, Clone)]
+struct LineState
+{
+    line: i64,
+    statement: usize,
+    snapshot: Snapshot
+}
+
+impl Chip
+{
+    pub fn new(name: &str, program: Program) -> Chip
+    {
+        Chip::with_environment(program, Environment::new(name))
+    }
+
+    /// Tokenizes and parses the source into the program for a new professional chip
+    pub fn from_source(name: &str, source: String) -> Result<Chip, YoloxideError>
+    {
+        Chip::from_source_with_limits(name, source, ChipLimits::default())
+    }
+
+    /// Tokenizes and parses the source for a chip with the given limits.
+    /// Errors if the program doesn't fit on the chip.
+    pub fn from_source_with_limits(name: &str, source: String, limits: ChipLimits) -> Result<Chip, YoloxideError>
+    {
+        let tokens = tokenizer::tokenize(source)?;
+        let program = parser::parse_program_with_limits(&mut TokenWindow::from(tokens), &limits)?;
+
+        let mut env = Environment::new(name);
+        env.limits = limits;
+
+        Ok(Chip::with_environment(program, env))
+    }
+
+    /// Makes a chip running the program in the environment, which switches to the program's symbol table
+    pub fn with_environment(program: Program, mut env: Environment) -> Chip
+    {
+        env.use_symbols(program.symbols());
+
+        Chip {
+            program,
+            env,
+            devices: Devices::new(),
+            ticks: 0,
+            line_state: None
+        }
+    }
+
+    /// Returns how many lines the chip has executed so far
+    pub fn ticks(&self) -> u64
+    {
+        self.ticks
+    }
+
+    /// Returns the line and the index of the statement on it that will be executed next
+    pub fn position(&self) -> (i64, usize)
+    {
+        match &self.line_state
+        {
+            Some(state) => (state.line, state.statement),
+            None => (self.env.next_line, 0)
+        }
+    }
+
+    /// Whether the chip has stopped part way through a line
+    pub fn is_mid_line(&self) -> bool
+    {
+        self.line_state.is_some()
+    }
+
+    /// Executes the line at `env.next_line`, which counts as one tick.
+    /// Lines past the end of the program are treated as empty lines.
+    /// If the chip is part way through a line, the rest of that line is executed instead.
+    pub fn step(&mut self) -> Result<(), EvaluationError>
+    {
+        while !self.step_statement()? {}
+        Ok(())
+    }
+
+    /// Executes a single statement, starting a new line first if needed.
+    /// Returns whether that finished the line.
+    pub fn step_statement(&mut self) -> Result<bool, EvaluationError>
+    {
+        let mut state = match self.line_state.take()
+        {
+            Some(state) => state,
+            None => {
+                self.ticks += 1;
+
+                let snapshot = self.devices.before_tick(self.env.global_context_mut());
+                let line = interpreter::begin_line(&mut self.env);
+
+                LineState {
+                    line,
+                    statement: 0,
+                    snapshot
+                }
+            }
+        };
+
+        let index = state.line - 1;
+        let statements = if index >= 0
+        {
+            self.program.0.get(index as usize).map(|line| line.0.as_slice())
+        }
+        else
+        {
+            None
+        };
+
+        // An empty line still takes a tick and moves us on to the next one
+        let statements = statements.unwrap_or_default();
+
+        let result = match statements.get(state.statement)
+        {
+            Some(statement) => interpreter::evaluate_line_statement(&mut self.env, state.line, state.statement, statement),
+            None => Ok(false)
+        };
+
+        state.statement += 1;
+
+        match result
+        {
+            Ok(true) if state.statement < statements.len() => {
+                self.line_state = Some(state);
+                Ok(false)
+            },
+            result => {
+                interpreter::end_line(&mut self.env);
+                self.devices.after_tick(self.env.global_context(), state.snapshot);
+
+                result.map(|_| true)
+            }
+        }
+    }
+
+    /// Jumps to the line like a goto would, skipping the rest of the current line if the chip is part way through one
+    pub fn goto_line(&mut self, line: i64)
+    {
+        if let Some(state) = self.line_state.take()
+        {
+            interpreter::end_line(&mut self.env);
+            self.devices.after_tick(self.env.global_context(), state.snapshot);
+        }
+
+        self.env.next_line = line;
+    }
+
+    /// Executes `count` ticks, stopping at the first error
+    pub fn run_ticks(&mut self, count: u64) -> Result<(), EvaluationError>
+    {
+        for _ in 0..count
+        {
+            self.step()?;
+        }
+
+        Ok(())
+    }
+
+    /// Executes ticks until the predicate returns true for the environment.
+    /// The predicate is checked before every tick, so this may never return if it's never satisfied.
+    /// Returns the number of ticks that were executed.
+    pub fn run_until<F>(&mut self, mut predicate: F) -> Result<u64, EvaluationError>
+    where F: FnMut(&Environment) -> bool
+    {
+        let mut count = 0;
+        while !predicate(&self.env)
+        {
+            self.step()?;
+            count += 1;
+        }
+
+        Ok(count)
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    use crate::types::TokenWindow;
+    use crate::types::ChipKind;
+    use crate::types::ast::value::LiteralValue;
+    use crate::environment::ContextMap;
+    use crate::tokenizer;
+    use crate::parser;
+
+    fn chip(source: &str) -> Chip
+    {
+        let tokens = tokenizer::tokenize(String::from(source)).unwrap();
+        let program = parser::parse_program(&mut TokenWindow::from(tokens)).unwrap();
+
+        Chip::new("test", program)
+    }
+
+    #[test]
+    fn steps_run_one_line_each()
+    {
+        let mut chip = chip("a = 1\nb = a + 1\n");
+
+        chip.step().unwrap();
+        assert_eq!(chip.env.get_val("a"), LiteralValue::from(1));
+        assert_eq!(chip.env.get_val("b"), LiteralValue::from(0));
+        assert_eq!((chip.ticks(), chip.env.next_line), (1, 2));
+
+        chip.step().unwrap();
+        assert_eq!(chip.env.get_val("b"), LiteralValue::from(2));
+        assert_eq!((chip.ticks(), chip.env.next_line), (2, 3));
+    }
+
+    #[test]
+    fn gotos_pick_the_next_line()
+    {
+        let mut chip = chip("a += 1 goto 3\nb = 1\ngoto 1\n");
+        chip.run_ticks(3).unwrap();
+
+        assert_eq!(chip.env.get_val("a"), LiteralValue::from(2));
+        assert_eq!(chip.env.get_val("b"), LiteralValue::from(0));
+        assert_eq!(chip.env.next_line, 3);
+    }
+
+    #[test]
+    fn lines_past_the_end_are_empty_and_wrap_back_to_line_1()
+    {
+        let mut chip = chip("a += 1\n");
+
+        chip.run_ticks(20).unwrap();
+        assert_eq!(chip.env.get_val("a"), LiteralValue::from(1));
+        assert_eq!(chip.env.next_line, 1);
+
+        chip.step().unwrap();
+        assert_eq!(chip.env.get_val("a"), LiteralValue::from(2));
+    }
+
+    #[test]
+    fn chips_wrap_at_their_last_line()
+    {
+        let mut chip = Chip::from_source_with_limits("test", String::from("a += 1\n"), ChipKind::Basic.into()).unwrap();
+
+        chip.run_ticks(12).unwrap();
+        assert_eq!(chip.env.next_line, 1);
+
+        chip.step().unwrap();
+        assert_eq!(chip.env.get_val("a"), LiteralValue::from(2));
+    }
+
+    #[test]
+    fn running_stops_at_the_first_error()
+    {
+        let mut chip = chip("a += 1\nb = 1 / 0\nc = 1\n");
+
+        assert!(chip.run_ticks(5).is_err());
+        assert_eq!(chip.ticks(), 2);
+        assert_eq!(chip.env.get_val("c"), LiteralValue::from(0));
+    }
+
+    #[test]
+    fn run_until_counts_the_ticks_it_ran()
+    {
+        let mut chip = chip("a += 1 goto 1\n");
+
+        let ticks = chip.run_until(|env| env.get_val("a") == LiteralValue::from(5)).unwrap();
+        assert_eq!(ticks, 5);
+        assert_eq!(chip.run_until(|env| env.get_val("a") == LiteralValue::from(5)).unwrap(), 0);
+    }
+}
@@ -0,0 +1,299 @@
+use std::fmt;
+use std::collections::BTreeSet;
+
+use crate::types::ast::{
+    statement::Statement,
+    value::LiteralValue,
+};
+use crate::types::{EvaluationError, YoloxideError, TokenWindow};
+
+use crate::environment::{ContextMap, normalize_ident};
+use crate::chip::Chip;
+use crate::tokenizer;
+use crate::parser;
+use crate::interpreter::{self, ControlFlow};
+
+/// Why the debugger stopped running the chip
+#[derive(Debug, Clone, PartialEq)]
+pub enum DebugEvent
+{
+    /// The step that was asked for is done
+    Stepped,
+    /// The chip is about to start a line with a breakpoint on it
+    Breakpoint(i64),
+    /// A statement wrote to a watched variable, even if the value stayed the same
+    Watch { name: String, old: LiteralValue, new: LiteralValue },
+    /// A statement errored, the chip has moved on to the next line
+    Error(EvaluationError),
+    /// Ran for the maximum number of ticks without anything else stopping it
+    TickLimit
+}
+
+impl fmt::Display for DebugEvent
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+    {
+        match self
+        {
+            DebugEvent::Stepped => write!(f, "stepped"),
+            DebugEvent::Breakpoint(line) => write!(f, "hit breakpoint on line {}", line),
+            DebugEvent::Watch { name, old, new } => write!(f, "{} was written: {} -> {}", name, old, new),
+            DebugEvent::Error(error) => write!(f, "error: {}", error),
+            DebugEvent::TickLimit => write!(f, "stopped after running the maximum number of ticks"),
+        }
+    }
+}
+
+/// Steps a chip through its program, stopping at breakpoints on lines and on writes to watched variables
+#[derive(Debug, Clone)]
+pub struct Debugger
+{
+    pub chip: Chip,
+
+    breakpoints: BTreeSet<i64>,
+    watches: BTreeSet<String>
+}
+
+impl Debugger
+{
+    pub fn new(chip: Chip) -> Debugger
+    {
+        Debugger {
+            chip,
+            breakpoints: BTreeSet::new(),
+            watches: BTreeSet::new()
+        }
+    }
+
+    pub fn add_breakpoint(&mut self, line: i64)
+    {
+        self.breakpoints.insert(line);
+    }
+
+    /// Returns whether there was a breakpoint on the line
+    pub fn remove_breakpoint(&mut self, line: i64) -> bool
+    {
+        self.breakpoints.remove(&line)
+    }
+
+    pub fn breakpoints(&self) -> &BTreeSet<i64>
+    {
+        &self.breakpoints
+    }
+
+    /// Stops the debugger whenever a statement writes to the variable
+    pub fn add_watch(&mut self, name: &str)
+    {
+        self.watches.insert(normalize_ident(name).into_owned());
+    }
+
+    /// Returns whether the variable was being watched
+    pub fn remove_watch(&mut self, name: &str) -> bool
+    {
+        self.watches.remove(normalize_ident(name).as_ref())
+    }
+
+    pub fn watches(&self) -> &BTreeSet<String>
+    {
+        &self.watches
+    }
+
+    pub fn get(&self, name: &str) -> LiteralValue
+    {
+        self.chip.env.get_val(name)
+    }
+
+    pub fn set(&mut self, name: &str, value: LiteralValue)
+    {
+        self.chip.env.set_val(String::from(name), value);
+    }
+
+    /// Returns the statement that will be executed next, if the chip's current line has one
+    pub fn next_statement(&self) -> Option<&Statement>
+    {
+        let (line, statement) = self.chip.position();
+        if line < 1
+        {
+            return None;
+        }
+
+        self.chip.program.0.get(line as usize - 1)
+            .and_then(|line| line.0.get(statement))
+            .map(|statement| &statement.node)
+    }
+
+    /// Executes yolol statements in the chip's environment, for poking at its variables.
+    /// The chip only moves if one of them is a goto, which jumps to its line and skips the rest.
+    pub fn eval(&mut self, source: &str) -> Result<(), YoloxideError>
+    {
+        let tokens = tokenizer::tokenize(String::from(source))?;
+        let line = parser::parse_line(&mut TokenWindow::from(tokens))?;
+
+        for statement in &line.0
+        {
+            let flow = interpreter::evaluate_statement_ref(&mut self.chip.env, &statement.node)
+                .map_err(|error| error.with_span(statement.span))?;
+
+            if let ControlFlow::Goto(target) = flow
+            {
+                self.chip.goto_line(target);
+                break;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Executes the next statement
+    pub fn step_statement(&mut self) -> DebugEvent
+    {
+        match self.watched_step()
+        {
+            Ok((_, Some(event))) => event,
+            Ok((_, None)) => DebugEvent::Stepped,
+            Err(error) => DebugEvent::Error(error)
+        }
+    }
+
+    /// Executes the rest of the current line, or the whole next line if the chip isn't part way through one.
+    /// Stops early if a watched variable is written to.
+    pub fn step_line(&mut self) -> DebugEvent
+    {
+        loop
+        {
+            match self.watched_step()
+            {
+                Ok((_, Some(event))) => return event,
+                Ok((true, None)) => return DebugEvent::Stepped,
+                Ok((false, None)) => {},
+                Err(error) => return DebugEvent::Error(error)
+            }
+        }
+    }
+
+    /// Runs until a breakpoint, a watch or an error stops it, or `max_ticks` lines have been executed
+    pub fn resume(&mut self, max_ticks: u64) -> DebugEvent
+    {
+        let mut ticks = 0;
+
+        loop
+        {
+            let finished_line = match self.watched_step()
+            {
+                Ok((finished_line, None)) => finished_line,
+                Ok((_, Some(event))) => return event,
+                Err(error) => return DebugEvent::Error(error)
+            };
+
+            if finished_line
+            {
+                ticks += 1;
+
+                let (line, _) = self.chip.position();
+                if self.breakpoints.contains(&line)
+                {
+                    return DebugEvent::Breakpoint(line);
+                }
+
+                if ticks >= max_ticks
+                {
+                    return DebugEvent::TickLimit;
+                }
+            }
+        }
+    }
+
+    // Steps one statement, returning whether it finished the line and the watch event it caused, if any.
+    // Writes are picked up from the trace, so one is started for the step if the chip isn't already tracing.
+    fn watched_step(&mut self) -> Result<(bool, Option<DebugEvent>), EvaluationError>
+    {
+        if self.watches.is_empty()
+        {
+            return self.chip.step_statement().map(|finished_line| (finished_line, None));
+        }
+
+        let own_trace = self.chip.env.trace.is_none();
+        if own_trace
+        {
+            self.chip.env.start_trace();
+        }
+
+        let traced = self.chip.env.trace.as_ref().map_or(0, |trace| trace.entries.len());
+        let result = self.chip.step_statement();
+
+        let entries = if own_trace
+        {
+            self.chip.env.take_trace().map(|trace| trace.entries).unwrap_or_default()
+        }
+        else
+        {
+            self.chip.env.trace.as_ref().map(|trace| trace.entries[traced..].to_vec()).unwrap_or_default()
+        };
+
+        let finished_line = result?;
+
+        let event = entries.into_iter()
+            .flat_map(|entry| entry.writes)
+            .find(|write| self.watches.contains(&write.name))
+            .map(|write| DebugEvent::Watch {
+                name: write.name,
+                old: write.old,
+                new: write.new
+            });
+
+        Ok((finished_line, event))
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    fn debugger(source: &str) -> Debugger
+    {
+        Debugger::new(Chip::from_source("test", String::from(source)).unwrap())
+    }
+
+    #[test]
+    fn eval_goto_moves_the_chip()
+    {
+        let mut debugger = debugger("a = 1 b = 2\nc = 3\nd = 4\n");
+        debugger.step_statement();
+
+        debugger.eval("goto 3 e = 5").unwrap();
+
+        assert_eq!(debugger.chip.position(), (3, 0));
+        assert_eq!(debugger.get("b"), LiteralValue::from(0));
+        assert_eq!(debugger.get("e"), LiteralValue::from(0));
+
+        debugger.step_line();
+        assert_eq!(debugger.get("d"), LiteralValue::from(4));
+    }
+
+    #[test]
+    fn watches_stop_on_writes_that_keep_the_value()
+    {
+        let mut debugger = debugger("a = 1\nb = a a = a\n");
+        debugger.add_watch("A");
+        debugger.step_line();
+
+        assert_eq!(debugger.step_line(), DebugEvent::Watch {
+            name: String::from("a"),
+            old: LiteralValue::from(1),
+            new: LiteralValue::from(1)
+        });
+        assert_eq!(debugger.get("b"), LiteralValue::from(1));
+    }
+
+    #[test]
+    fn watches_leave_an_existing_trace_going()
+    {
+        let mut debugger = debugger("a = 1\n");
+        debugger.chip.env.start_trace();
+        debugger.add_watch("a");
+
+        assert!(matches!(debugger.step_statement(), DebugEvent::Watch { .. }));
+        assert_eq!(debugger.chip.env.trace.as_ref().unwrap().entries.len(), 1);
+    }
+}
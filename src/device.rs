@@ -0,0 +1,538 @@
+use std::fmt;
+use std::any::Any;
+
+use crate::types::ast::value::LiteralValue;
+
+use crate::environment::Variables;
+
+/// Simulated hardware attached to data fields, like a button or a lamp.
+///
+/// Every tick the device is first asked for the values it wants scripts to read,
+/// then after the scripts have run it's told about any of its fields they wrote to.
+/// Devices are owned by the chip or network they're attached to, so they have to be `Send`
+/// for chips to be run on other threads.
+pub trait Device: fmt::Debug + Send + Any
+{
+    /// The data fields the device owns, like `:ButtonState`
+    fn fields(&self) -> Vec<String>;
+
+    /// Returns the value the device puts on the field before the scripts run this tick.
+    /// Returning None leaves whatever value the field already has.
+    fn read(&mut self, field: &str) -> Option<LiteralValue>;
+
+    /// Called after the scripts ran when a field the device owns was given a new value
+    fn write(&mut self, field: &str, value: &LiteralValue);
+
+    /// Copies the device into a new box. Used when the chip or network it's on is cloned.
+    fn box_clone(&self) -> Box<dyn Device>;
+}
+
+/// A set of devices sharing the data fields of a chip or network.
+///
+/// The set owns its devices. Cloning it clones every device, so a cloned chip
+/// has its own hardware and running it doesn't change the original's devices.
+/// Use the id from `register` to look at a device after the chip has run.
+#[derive(Debug, Default)]
+pub struct Devices
+{
+    devices: Vec<Box<dyn Device>>
+}
+
+/// Refers to a device registered with a `Devices`, and to its copy in any clone of it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct DeviceId(usize);
+
+impl Clone for Devices
+{
+    fn clone(&self) -> Devices
+    {
+        Devices {
+            devices: self.devices.iter().map(|device| device.box_clone()).collect()
+        }
+    }
+}
+
+/// Values of the fields the devices own, from just before the scripts ran.
+/// Each is kept with the index of its device and the field as the device named it.
+pub type Snapshot = Vec<(usize, String, LiteralValue)>;
+
+impl Devices
+{
+    pub fn new() -> Devices
+    {
+        Devices::default()
+    }
+
+    /// Adds a device and returns the id to get it back with
+    pub fn register<D: Device>(&mut self, device: D) -> DeviceId
+    {
+        self.devices.push(Box::new(device));
+        DeviceId(self.devices.len() - 1)
+    }
+
+    /// Returns the device with the given id, if it's a `D`
+    pub fn get<D: Device>(&self, id: DeviceId) -> Option<&D>
+    {
+        let device: &dyn Any = self.devices.get(id.0)?.as_ref();
+        device.downcast_ref()
+    }
+
+    /// Returns the device with the given id mutably, if it's a `D`
+    pub fn get_mut<D: Device>(&mut self, id: DeviceId) -> Option<&mut D>
+    {
+        let device: &mut dyn Any = self.devices.get_mut(id.0)?.as_mut();
+        device.downcast_mut()
+    }
+
+    pub fn is_empty(&self) -> bool
+    {
+        self.devices.is_empty()
+    }
+
+    /// Lets every device put its values on the fields, then remembers what they were.
+    /// Every device has read before anything is remembered, so a field that two devices share
+    /// is remembered with the value the scripts will actually see.
+    pub fn before_tick(&mut self, fields: &mut Variables) -> Snapshot
+    {
+        for device in &mut self.devices
+        {
+            for field in device.fields()
+            {
+                if let Some(value) = device.read(&field)
+                {
+                    fields.set(&field, value);
+                }
+            }
+        }
+
+        let mut snapshot = Vec::new();
+        for (index, device) in self.devices.iter().enumerate()
+        {
+            for field in device.fields()
+            {
+                let value = fields.get(&field).cloned().unwrap_or_else(LiteralValue::get_false);
+                snapshot.push((index, field, value));
+            }
+        }
+
+        snapshot
+    }
+
+    /// Tells the devices about any of their fields that changed since `before_tick`
+    pub fn after_tick(&mut self, fields: &Variables, snapshot: Snapshot)
+    {
+        for (index, field, old_value) in snapshot
+        {
+            let new_value = fields.get(&field).cloned().unwrap_or_else(LiteralValue::get_false);
+            if new_value == old_value
+            {
+                continue;
+            }
+
+            if let Some(device) = self.devices.get_mut(index)
+            {
+                device.write(&field, &new_value);
+            }
+        }
+    }
+}
+
+/// A push button. Pressing or releasing it sets its field to 1 or 0 on the next tick.
+#[derive(Debug, Clone)]
+pub struct Button
+{
+    field: String,
+    pressed: bool,
+    changed: bool
+}
+
+impl Button
+{
+    pub fn new(field: &str) -> Button
+    {
+        Button {
+            field: String::from(field),
+            pressed: false,
+            changed: false
+        }
+    }
+
+    pub fn press(&mut self)
+    {
+        self.pressed = true;
+        self.changed = true;
+    }
+
+    pub fn release(&mut self)
+    {
+        self.pressed = false;
+        self.changed = true;
+    }
+
+    pub fn is_pressed(&self) -> bool
+    {
+        self.pressed
+    }
+}
+
+impl Device for Button
+{
+    fn fields(&self) -> Vec<String>
+    {
+        vec![self.field.clone()]
+    }
+
+    fn read(&mut self, _field: &str) -> Option<LiteralValue>
+    {
+        if !self.changed
+        {
+            return None;
+        }
+
+        self.changed = false;
+        Some(LiteralValue::from(self.pressed))
+    }
+
+    // Like in game, a script can reset the button state itself
+    fn write(&mut self, _field: &str, value: &LiteralValue)
+    {
+        self.pressed = *value != LiteralValue::get_false();
+    }
+
+    fn box_clone(&self) -> Box<dyn Device>
+    {
+        Box::new(self.clone())
+    }
+}
+
+/// A lamp that's on whenever its field isn't 0
+#[derive(Debug, Clone)]
+pub struct Lamp
+{
+    field: String,
+    on: bool
+}
+
+impl Lamp
+{
+    pub fn new(field: &str) -> Lamp
+    {
+        Lamp {
+            field: String::from(field),
+            on: false
+        }
+    }
+
+    pub fn is_on(&self) -> bool
+    {
+        self.on
+    }
+}
+
+impl Device for Lamp
+{
+    fn fields(&self) -> Vec<String>
+    {
+        vec![self.field.clone()]
+    }
+
+    fn read(&mut self, _field: &str) -> Option<LiteralValue>
+    {
+        None
+    }
+
+    fn write(&mut self, _field: &str, value: &LiteralValue)
+    {
+        self.on = *value != LiteralValue::get_false();
+    }
+
+    fn box_clone(&self) -> Box<dyn Device>
+    {
+        Box::new(self.clone())
+    }
+}
+
+/// A text panel that shows the last value written to its field
+#[derive(Debug, Clone)]
+pub struct TextPanel
+{
+    field: String,
+    text: String
+}
+
+impl TextPanel
+{
+    pub fn new(field: &str) -> TextPanel
+    {
+        TextPanel {
+            field: String::from(field),
+            text: String::new()
+        }
+    }
+
+    pub fn text(&self) -> &str
+    {
+        &self.text
+    }
+}
+
+impl Device for TextPanel
+{
+    fn fields(&self) -> Vec<String>
+    {
+        vec![self.field.clone()]
+    }
+
+    fn read(&mut self, _field: &str) -> Option<LiteralValue>
+    {
+        None
+    }
+
+    fn write(&mut self, _field: &str, value: &LiteralValue)
+    {
+        self.text = match value
+        {
+            LiteralValue::StringVal(string) => string.clone(),
+            number => number.to_string()
+        };
+    }
+
+    fn box_clone(&self) -> Box<dyn Device>
+    {
+        Box::new(self.clone())
+    }
+}
+
+/// A sensor that puts its current reading on its field every tick, overwriting anything a script wrote
+#[derive(Debug, Clone)]
+pub struct Sensor
+{
+    field: String,
+    value: LiteralValue
+}
+
+impl Sensor
+{
+    pub fn new(field: &str, value: LiteralValue) -> Sensor
+    {
+        Sensor {
+            field: String::from(field),
+            value
+        }
+    }
+
+    pub fn set_value(&mut self, value: LiteralValue)
+    {
+        self.value = value;
+    }
+
+    pub fn value(&self) -> &LiteralValue
+    {
+        &self.value
+    }
+}
+
+impl Device for Sensor
+{
+    fn fields(&self) -> Vec<String>
+    {
+        vec![self.field.clone()]
+    }
+
+    fn read(&mut self, _field: &str) -> Option<LiteralValue>
+    {
+        Some(self.value.clone())
+    }
+
+    fn write(&mut self, _field: &str, _value: &LiteralValue) {}
+
+    fn box_clone(&self) -> Box<dyn Device>
+    {
+        Box::new(self.clone())
+    }
+}
+
+/// A thruster that turns the value written to its field into a thrust level, clamped to -100..=100 percent
+#[derive(Debug, Clone)]
+pub struct Thruster
+{
+    field: String,
+    thrust: f64
+}
+
+impl Thruster
+{
+    pub fn new(field: &str) -> Thruster
+    {
+        Thruster {
+            field: String::from(field),
+            thrust: 0.0
+        }
+    }
+
+    pub fn thrust(&self) -> f64
+    {
+        self.thrust
+    }
+}
+
+impl Device for Thruster
+{
+    fn fields(&self) -> Vec<String>
+    {
+        vec![self.field.clone()]
+    }
+
+    fn read(&mut self, _field: &str) -> Option<LiteralValue>
+    {
+        None
+    }
+
+    // Strings don't mean anything to a thruster, so it just cuts out
+    fn write(&mut self, _field: &str, value: &LiteralValue)
+    {
+        self.thrust = match value
+        {
+            LiteralValue::NumberVal(num) => num.float_value().clamp(-100.0, 100.0),
+            LiteralValue::StringVal(_) => 0.0
+        };
+    }
+
+    fn box_clone(&self) -> Box<dyn Device>
+    {
+        Box::new(self.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    use crate::chip::Chip;
+    use crate::network::Network;
+    use crate::environment::ContextMap;
+
+    #[test]
+    fn cloned_chips_have_their_own_devices()
+    {
+        let mut chip = Chip::from_source("test", String::from(":lamp = 1\n")).unwrap();
+        let lamp = chip.devices.register(Lamp::new(":Lamp"));
+        let mut clone = chip.clone();
+
+        clone.run_ticks(1).unwrap();
+
+        assert!(clone.devices.get::<Lamp>(lamp).unwrap().is_on());
+        assert!(!chip.devices.get::<Lamp>(lamp).unwrap().is_on());
+    }
+
+    #[test]
+    fn devices_are_only_returned_as_their_own_type()
+    {
+        let mut devices = Devices::new();
+        let button = devices.register(Button::new(":Button"));
+
+        assert!(devices.get::<Lamp>(button).is_none());
+
+        devices.get_mut::<Button>(button).unwrap().press();
+        assert!(devices.get::<Button>(button).unwrap().is_pressed());
+    }
+
+    #[test]
+    fn chips_and_networks_are_send()
+    {
+        fn assert_send<T: Send>() {}
+
+        assert_send::<Chip>();
+        assert_send::<Network>();
+    }
+
+    #[test]
+    fn scripts_see_button_presses_and_can_reset_them()
+    {
+        let mut chip = Chip::from_source("test", String::from("a = :button :button = 0 goto 1\n")).unwrap();
+        let button = chip.devices.register(Button::new(":Button"));
+
+        chip.devices.get_mut::<Button>(button).unwrap().press();
+        chip.run_ticks(1).unwrap();
+
+        assert_eq!(chip.env.get_val("a"), LiteralValue::from(1));
+        assert!(!chip.devices.get::<Button>(button).unwrap().is_pressed());
+    }
+
+    #[test]
+    fn sensors_overwrite_what_scripts_wrote()
+    {
+        let mut chip = Chip::from_source("test", String::from("a = :temp :temp = 5 goto 1\n")).unwrap();
+        let sensor = chip.devices.register(Sensor::new(":Temp", LiteralValue::from(20)));
+
+        chip.run_ticks(2).unwrap();
+        assert_eq!(chip.env.get_val("a"), LiteralValue::from(20));
+
+        chip.devices.get_mut::<Sensor>(sensor).unwrap().set_value(LiteralValue::from(30));
+        chip.run_ticks(1).unwrap();
+        assert_eq!(chip.env.get_val("a"), LiteralValue::from(30));
+    }
+
+    #[test]
+    fn outputs_follow_what_scripts_wrote()
+    {
+        let mut chip = Chip::from_source("test", String::from(":thrust = 250 :panel = \"hi\"\n:thrust = \"x\" :panel = 1.5\n")).unwrap();
+        let thruster = chip.devices.register(Thruster::new(":Thrust"));
+        let panel = chip.devices.register(TextPanel::new(":Panel"));
+
+        chip.run_ticks(1).unwrap();
+        assert_eq!(chip.devices.get::<Thruster>(thruster).unwrap().thrust(), 100.0);
+        assert_eq!(chip.devices.get::<TextPanel>(panel).unwrap().text(), "hi");
+
+        chip.run_ticks(1).unwrap();
+        assert_eq!(chip.devices.get::<Thruster>(thruster).unwrap().thrust(), 0.0);
+        assert_eq!(chip.devices.get::<TextPanel>(panel).unwrap().text(), "1.5");
+    }
+
+    // Remembers every write it's told about
+    #[derive(Debug, Clone)]
+    struct Recorder
+    {
+        field: String,
+        writes: Vec<LiteralValue>
+    }
+
+    impl Device for Recorder
+    {
+        fn fields(&self) -> Vec<String>
+        {
+            vec![self.field.clone()]
+        }
+
+        fn read(&mut self, _field: &str) -> Option<LiteralValue>
+        {
+            None
+        }
+
+        fn write(&mut self, _field: &str, value: &LiteralValue)
+        {
+            self.writes.push(value.clone());
+        }
+
+        fn box_clone(&self) -> Box<dyn Device>
+        {
+            Box::new(self.clone())
+        }
+    }
+
+    #[test]
+    fn devices_sharing_a_field_only_hear_about_script_writes()
+    {
+        let mut chip = Chip::from_source("test", String::from("a = :temp\n:temp = 5\n")).unwrap();
+        let recorder = chip.devices.register(Recorder { field: String::from(":temp"), writes: Vec::new() });
+        chip.devices.register(Sensor::new(":Temp", LiteralValue::from(20)));
+
+        chip.run_ticks(1).unwrap();
+        assert!(chip.devices.get::<Recorder>(recorder).unwrap().writes.is_empty());
+
+        chip.run_ticks(1).unwrap();
+        assert_eq!(chip.devices.get::<Recorder>(recorder).unwrap().writes, [LiteralValue::from(5)]);
+    }
+}
+
@@ -1,11 +1,37 @@
 use std::fmt;
-use std::collections::HashMap;
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap};
 
-use serde::{Serialize, Deserialize};
+use serde::{Serialize, Deserialize, Serializer, Deserializer};
 
 use yolol_number::prelude::*;
 
 use crate::types::ast::value::LiteralValue;
+use crate::types::EvaluationError;
+use crate::types::ChipLimits;
+use crate::types::{Ident, Symbol, SymbolTable};
+use crate::trace::Trace;
+
+/// How the interpreter reacts to a runtime error
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default, Serialize, Deserialize)]
+pub enum ErrorMode
+{
+    /// The error is returned to the caller, which is the default
+    #[default]
+    Propagate,
+    /// Like in game, runtime errors skip the rest of the line and the chip carries on with the next line.
+    /// The error is recorded in the environment's runtime errors.
+    AbortLine
+}
+
+/// A record of a runtime error that aborted the rest of its line
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RuntimeError
+{
+    pub line: i64,
+    pub statement: usize,
+    pub error: EvaluationError
+}
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Environment
@@ -15,8 +41,21 @@ pub struct Environment
     pub next_line: i64,
     pub error: String,
 
-    local_context: HashMap<String, LiteralValue>,
-    global_context: HashMap<String, LiteralValue>
+    #[serde(default)]
+    pub error_mode: ErrorMode,
+    #[serde(default)]
+    pub runtime_errors: Vec<RuntimeError>,
+    #[serde(default)]
+    pub limits: ChipLimits,
+    /// Every statement executed while tracing is on
+    #[serde(skip)]
+    pub trace: Option<Trace>,
+
+    /// Local variables, kept in slots indexed by the symbols of their names
+    #[serde(rename = "local_context")]
+    locals: Locals,
+    /// Data fields stay keyed by name, since networks and devices share them between chips
+    global_context: Variables
 }
 
 impl Environment
@@ -29,15 +68,19 @@ impl Environment
         // We start at the first line on a chip
         let next_line = 1;
 
-        let local_context = HashMap::new();
-        let global_context = HashMap::new();
+        let locals = Locals::default();
+        let global_context = Variables::new();
 
         Environment {
             name,
             version,
             next_line,
             error: String::new(),
-            local_context,
+            error_mode: ErrorMode::default(),
+            runtime_errors: Vec::new(),
+            limits: ChipLimits::default(),
+            trace: None,
+            locals,
             global_context,
         }
     }
@@ -46,6 +89,137 @@ impl Environment
     {
         self.next_line = num.bound().get_value();
     }
+
+    /// Records an error that aborted the rest of a line
+    pub fn record_runtime_error(&mut self, line: i64, statement: usize, error: EvaluationError)
+    {
+        self.runtime_errors.push(RuntimeError {
+            line,
+            statement,
+            error
+        });
+    }
+
+    /// Starts recording a trace of every statement executed, replacing any trace already being recorded
+    pub fn start_trace(&mut self)
+    {
+        self.trace = Some(Trace::new());
+    }
+
+    /// Stops tracing and returns the trace recorded so far
+    pub fn take_trace(&mut self) -> Option<Trace>
+    {
+        self.trace.take()
+    }
+
+    /// The symbols of the locals' slots
+    pub fn symbols(&self) -> &SymbolTable
+    {
+        &self.locals.symbols
+    }
+
+    /// Switches to a program's symbol table, so its idents can use their symbols to get at the slots.
+    /// Any locals that are already set keep their values.
+    pub fn use_symbols(&mut self, symbols: &SymbolTable)
+    {
+        if self.locals.symbols.includes(symbols)
+        {
+            return;
+        }
+
+        let old = std::mem::replace(&mut self.locals, Locals::with_symbols(symbols.clone()));
+        for (name, value) in old.iter()
+        {
+            let symbol = self.locals.symbols.intern(name);
+            self.locals.set(symbol, value.clone());
+        }
+    }
+
+    /// Interns a local's name, for getting at its slot with `get_local` and `set_local`
+    pub fn intern(&mut self, name: &str) -> Symbol
+    {
+        self.locals.symbols.intern(name)
+    }
+
+    /// Returns the local variable with the symbol, if it's been set.
+    /// Symbols from a table the environment isn't using aren't set.
+    pub fn get_local(&self, symbol: Symbol) -> Option<&LiteralValue>
+    {
+        if !self.locals.symbols.contains(symbol)
+        {
+            return None;
+        }
+
+        self.locals.values.get(symbol.index()).and_then(Option::as_ref)
+    }
+
+    /// Sets the local variable with the symbol, returning whether it was set.
+    /// Symbols from a table the environment isn't using can't be, so they're ignored.
+    pub fn set_local(&mut self, symbol: Symbol, value: LiteralValue) -> bool
+    {
+        if !self.locals.symbols.contains(symbol)
+        {
+            return false;
+        }
+
+        self.locals.set(symbol, value);
+        true
+    }
+
+    /// Reads a variable. Locals interned with the environment's symbol table skip looking up the name.
+    pub fn get(&self, ident: &Ident) -> LiteralValue
+    {
+        let value = if ident.is_data_field()
+        {
+            self.global_context.get_normalized(&ident.key)
+        }
+        else
+        {
+            match ident.symbol
+            {
+                Some(symbol) if self.locals.symbols.contains(symbol) => self.get_local(symbol),
+                _ => self.locals.symbols.get(&ident.key).and_then(|symbol| self.get_local(symbol))
+            }
+        };
+
+        value.cloned().unwrap_or_else(LiteralValue::get_false)
+    }
+
+    /// Writes a variable. Locals interned with the environment's symbol table skip looking up the name.
+    pub fn set(&mut self, ident: &Ident, value: LiteralValue)
+    {
+        if ident.is_data_field()
+        {
+            self.global_context.set_normalized(&ident.key, &ident.name, value);
+            return;
+        }
+
+        let symbol = match ident.symbol
+        {
+            Some(symbol) if self.locals.symbols.contains(symbol) => symbol,
+            _ => self.locals.symbols.intern(&ident.name)
+        };
+
+        self.locals.set(symbol, value);
+    }
+
+    /// The chip's own variables that have been set, by the spelling they were first interned with
+    pub fn local_context(&self) -> impl Iterator<Item = (&str, &LiteralValue)>
+    {
+        self.locals.iter()
+    }
+
+    /// The data fields the chip can see
+    pub fn global_context(&self) -> &Variables
+    {
+        &self.global_context
+    }
+
+    /// Mutable access to the data fields, for things outside the chip that share them, like a network
+    pub fn global_context_mut(&mut self) -> &mut Variables
+    {
+        &mut self.global_context
+    }
 }
 
 impl fmt::Display for Environment
@@ -57,14 +231,14 @@ impl fmt::Display for Environment
 
         out_string += "\n";
         out_string += "Local context:\n";
-        for (key, value) in &self.local_context
+        for (key, value) in self.local_context()
         {
             out_string += &format!("Key: '{}', Value: '{}'\n", key, value);
         }
 
         out_string += "\n";
         out_string += "Global context:\n";
-        for (key, value) in &self.global_context
+        for (key, value) in self.global_context.iter()
         {
             out_string += &format!("Key: '{}', Value: '{}'\n", key, value);
         }
@@ -84,30 +258,356 @@ impl ContextMap for Environment
     fn get_val(&self, ident: &str) -> LiteralValue
     {
         // Means the ident is referencing a data field, aka the global context
-        if let Some(':') = ident.chars().next()
+        let value = if ident.starts_with(':')
         {
             self.global_context.get(ident)
-                .unwrap_or(&LiteralValue::get_false())
-                .clone()
         }
         else
         {
-            self.local_context.get(ident)
-                .unwrap_or(&LiteralValue::get_false())
-                .clone()
-        }
+            // A name that was never interned can't have been set
+            self.locals.symbols.get(ident).and_then(|symbol| self.get_local(symbol))
+        };
+
+        value.cloned().unwrap_or_else(LiteralValue::get_false)
     }
 
     fn set_val(&mut self, ident: String, value: LiteralValue)
     {
          // Means the ident is referencing a data field, aka the global context
-        if let Some(':') = ident.chars().next()
+        if ident.starts_with(':')
         {
-            self.global_context.insert(ident, value);
+            self.global_context.set(&ident, value);
         }
         else
         {
-            self.local_context.insert(ident, value);
+            let symbol = self.locals.symbols.intern(&ident);
+            self.locals.set(symbol, value);
         }
     }
 }
+
+/// Yolol names are case insensitive, so `A`, `a`, `:Door` and `:door` are all stored under their lowercase form.
+/// Names that are already lowercase aren't copied.
+pub fn normalize_ident(ident: &str) -> Cow<'_, str>
+{
+    if ident.bytes().any(|byte| byte.is_ascii_uppercase())
+    {
+        Cow::Owned(ident.to_ascii_lowercase())
+    }
+    else
+    {
+        Cow::Borrowed(ident)
+    }
+}
+
+// A variable's value, along with the spelling of its name from when it was first set
+#[derive(Debug, Clone, PartialEq)]
+struct Variable
+{
+    name: String,
+    value: LiteralValue
+}
+
+/// Variables keyed by their normalized name, so any spelling of a name gets at the same variable.
+/// The first spelling a variable was set with is kept for display.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Variables(HashMap<String, Variable>);
+
+impl Variables
+{
+    pub fn new() -> Variables
+    {
+        Variables(HashMap::new())
+    }
+
+    pub fn get(&self, name: &str) -> Option<&LiteralValue>
+    {
+        self.get_normalized(&normalize_ident(name))
+    }
+
+    /// Gets a variable by a name that's already normalized, skipping normalizing it again
+    pub fn get_normalized(&self, key: &str) -> Option<&LiteralValue>
+    {
+        self.0.get(key).map(|variable| &variable.value)
+    }
+
+    pub fn set(&mut self, name: &str, value: LiteralValue)
+    {
+        let key = normalize_ident(name);
+        self.set_normalized(&key, name, value);
+    }
+
+    /// Sets a variable by a name that's already normalized. `name` is only used if the variable is new.
+    pub fn set_normalized(&mut self, key: &str, name: &str, value: LiteralValue)
+    {
+        match self.0.get_mut(key)
+        {
+            Some(variable) => variable.value = value,
+            None => {
+                self.0.insert(String::from(key), Variable {
+                    name: String::from(name),
+                    value
+                });
+            }
+        }
+    }
+
+    /// Moves every variable from the other set into this one.
+    /// Variables that are in both take the other's value, but keep this set's spelling.
+    pub fn append(&mut self, other: Variables)
+    {
+        for (key, variable) in other.0
+        {
+            self.set_normalized(&key, &variable.name, variable.value);
+        }
+    }
+
+    /// The spelling the variable was first set with
+    pub fn name(&self, name: &str) -> Option<&str>
+    {
+        self.0.get(normalize_ident(name).as_ref()).map(|variable| variable.name.as_str())
+    }
+
+    /// Every variable, by the spelling it was first set with
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &LiteralValue)>
+    {
+        self.0.values().map(|variable| (variable.name.as_str(), &variable.value))
+    }
+
+    pub fn len(&self) -> usize
+    {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool
+    {
+        self.0.is_empty()
+    }
+}
+
+// Saved as a map of the names as they were spelled to values
+impl Serialize for Variables
+{
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    {
+        self.iter()
+            .collect::<BTreeMap<&str, &LiteralValue>>()
+            .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Variables
+{
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Variables, D::Error>
+    {
+        let mut variables = Variables::new();
+        for (name, value) in BTreeMap::<String, LiteralValue>::deserialize(deserializer)?
+        {
+            variables.set(&name, value);
+        }
+
+        Ok(variables)
+    }
+}
+
+// The slots of the locals, along with the symbol table they're indexed by. Variables that have never been set are None.
+#[derive(Debug, Clone, Default)]
+struct Locals
+{
+    symbols: SymbolTable,
+    values: Vec<Option<LiteralValue>>
+}
+
+impl Locals
+{
+    fn with_symbols(symbols: SymbolTable) -> Locals
+    {
+        Locals {
+            values: vec![None; symbols.len()],
+            symbols
+        }
+    }
+
+    // The symbol has to be from this table
+    fn set(&mut self, symbol: Symbol, value: LiteralValue)
+    {
+        let index = symbol.index();
+        if index >= self.values.len()
+        {
+            self.values.resize(index + 1, None);
+        }
+
+        self.values[index] = Some(value);
+    }
+
+    // The locals that are set, by name
+    fn iter(&self) -> impl Iterator<Item = (&str, &LiteralValue)>
+    {
+        self.symbols.names().iter()
+            .zip(&self.values)
+            .filter_map(|(name, value)| Some((name.as_str(), value.as_ref()?)))
+    }
+}
+
+// Saves locals as a map of names to values, since symbols are only meaningful with the table that interned them
+impl Serialize for Locals
+{
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    {
+        self.iter()
+            .collect::<BTreeMap<&str, &LiteralValue>>()
+            .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Locals
+{
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Locals, D::Error>
+    {
+        let mut locals = Locals::default();
+        for (name, value) in BTreeMap::<String, LiteralValue>::deserialize(deserializer)?
+        {
+            let symbol = locals.symbols.intern(&name);
+            locals.set(symbol, value);
+        }
+
+        Ok(locals)
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    use crate::chip::Chip;
+    use crate::types::TokenWindow;
+    use crate::tokenizer;
+    use crate::parser;
+    use crate::interpreter;
+    use crate::types::ast::program::Program;
+
+    fn parse(source: &str) -> Program
+    {
+        let tokens = tokenizer::tokenize(String::from(source)).unwrap();
+        parser::parse_program(&mut TokenWindow::from(tokens)).unwrap()
+    }
+
+    fn chip(source: &str) -> Chip
+    {
+        Chip::new("test", parse(source))
+    }
+
+    #[test]
+    fn only_the_programs_locals_are_interned()
+    {
+        let program = parse("a = 1 B = :door\nb += a :door = 2\n");
+
+        assert_eq!(program.symbols().names(), ["a", "B"]);
+        assert!(program.symbols().get(":door").is_none());
+
+        let chip = Chip::new("test", program);
+        assert_eq!(chip.env.symbols().len(), 2);
+    }
+
+    #[test]
+    fn idents_from_other_programs_are_looked_up_by_name()
+    {
+        let mut chip = chip("a = 1 b = 2\n");
+        chip.step().unwrap();
+
+        // b is the first symbol of this program, but the second of the chip's
+        let other = parse("c = b + 1 a = 5\n");
+        interpreter::evaluate_line(&mut chip.env, &other.0[0]).unwrap();
+
+        assert_eq!(chip.env.get_val("c"), LiteralValue::from(3));
+        assert_eq!(chip.env.get_val("a"), LiteralValue::from(5));
+        assert_eq!(chip.env.get_val("b"), LiteralValue::from(2));
+    }
+
+    #[test]
+    fn symbols_from_other_tables_are_never_set()
+    {
+        let mut env = Environment::new("test");
+        env.set_val(String::from("a"), LiteralValue::from(1));
+
+        let other = parse("b = 1 a = 2\n");
+        let symbol = other.symbols().get("b").unwrap();
+
+        assert!(!env.set_local(symbol, LiteralValue::from(5)));
+        assert_eq!(env.get_local(symbol), None);
+        assert_eq!(env.get_val("a"), LiteralValue::from(1));
+        assert_eq!(env.get_val("b"), LiteralValue::from(0));
+
+        let symbol = env.intern("b");
+        assert!(env.set_local(symbol, LiteralValue::from(5)));
+        assert_eq!(env.get_val("B"), LiteralValue::from(5));
+    }
+
+    #[test]
+    fn switching_symbol_tables_keeps_the_locals()
+    {
+        let mut env = Environment::new("test");
+        env.set_val(String::from("x"), LiteralValue::from(1));
+        env.set_val(String::from("Y"), LiteralValue::from(2));
+
+        let chip = Chip::with_environment(parse("y = x + 1\n"), env);
+
+        assert_eq!(chip.env.get_val("x"), LiteralValue::from(1));
+        assert_eq!(chip.env.get_val("y"), LiteralValue::from(2));
+        assert!(chip.program.symbols().names().iter().all(|name| chip.env.symbols().get(name).is_some()));
+    }
+
+    #[test]
+    fn locals_are_saved_by_name()
+    {
+        let mut chip = chip("b = 2 A = \"x\"\n");
+        chip.step().unwrap();
+
+        let json = serde_json::to_string(&chip.env).unwrap();
+        assert!(json.contains(r#""local_context":{"A":{"StringVal":"x"},"b":{"NumberVal":"2"}}"#), "{}", json);
+
+        let env: Environment = serde_json::from_str(&json).unwrap();
+        assert_eq!(env.get_val("a"), LiteralValue::from("x"));
+        assert_eq!(env.get_val("b"), LiteralValue::from(2));
+    }
+
+    #[test]
+    fn names_ignore_case()
+    {
+        let mut chip = chip("Abc = 1 aBC++ :Door = ABC\n");
+        chip.step().unwrap();
+
+        assert_eq!(chip.env.get_val("abc"), LiteralValue::from(2));
+        assert_eq!(chip.env.get_val(":DOOR"), LiteralValue::from(2));
+    }
+
+    #[test]
+    fn names_keep_their_first_spelling()
+    {
+        let mut chip = chip("Abc = 1 aBC++ :Door = ABC\n");
+        chip.step().unwrap();
+
+        assert_eq!(chip.program.symbols().names(), ["Abc"]);
+        assert_eq!(chip.env.global_context().name(":door"), Some(":Door"));
+
+        let display = chip.env.to_string();
+        assert!(display.contains("Key: 'Abc', Value: '2'"), "{}", display);
+        assert!(display.contains("Key: ':Door', Value: '2'"), "{}", display);
+    }
+
+    #[test]
+    fn variables_are_saved_by_their_spelling()
+    {
+        let mut variables = Variables::new();
+        variables.set("Abc", LiteralValue::from(1));
+        variables.set("ABC", LiteralValue::from(2));
+
+        let json = serde_json::to_string(&variables).unwrap();
+        assert_eq!(json, r#"{"Abc":{"NumberVal":"2"}}"#);
+
+        let loaded: Variables = serde_json::from_str(&json).unwrap();
+        assert_eq!(loaded.get("abc"), Some(&LiteralValue::from(2)));
+        assert_eq!(loaded.name("abc"), Some("Abc"));
+    }
+}
@@ -0,0 +1,542 @@
+use crate::types::ast::{
+    statement::Statement as Stat,
+    expression::Expression as Expr,
+    operators::Operator as Op,
+    value::Value,
+    line::Line,
+    program::Program
+};
+
+use crate::types::Spanned;
+
+/// How much whitespace goes between tokens
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum Spacing
+{
+    /// Spaces around binary operators and between statements, like `a = b + 1 c = 2`
+    #[default]
+    Normal,
+    /// Only the spaces yolol needs to tell tokens apart, like `a=b+1 c=2`
+    Compact
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum KeywordCase
+{
+    #[default]
+    Lower,
+    Upper
+}
+
+/// What to do with the parentheses in the program
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum ParenPolicy
+{
+    /// Keep every group that's in the program, so the output parses back to exactly the same tree
+    #[default]
+    Preserve,
+    /// Drop groups that don't change how the program parses
+    Minimal
+}
+
+/// The style a program is formatted in. The default is the standard format.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub struct FormatStyle
+{
+    pub spacing: Spacing,
+    pub keyword_case: KeywordCase,
+    pub parens: ParenPolicy
+}
+
+/// Prints programs as yolol source.
+///
+/// For any program the parser produced, formatting with `ParenPolicy::Preserve` gives source that parses back
+/// to the same program, which `same_code` can check. With `ParenPolicy::Minimal` only redundant groups are lost.
+/// Programs built by hand get whatever extra parentheses they need to parse back the way they're written.
+#[derive(Debug, Clone, Default)]
+pub struct Formatter
+{
+    pub style: FormatStyle
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+enum TokKind
+{
+    Ident,
+    Literal,
+    Keyword,
+    /// Binary and assignment operators
+    Op,
+    /// Unary operators written before their operand
+    Prefix,
+    /// Unary operators written after their operand
+    Postfix,
+    Open,
+    Close,
+    Comment
+}
+
+#[derive(Debug, Clone)]
+struct Tok
+{
+    text: String,
+    kind: TokKind
+}
+
+impl Tok
+{
+    fn new(text: impl Into<String>, kind: TokKind) -> Tok
+    {
+        Tok {
+            text: text.into(),
+            kind
+        }
+    }
+}
+
+// How tightly each kind of expression binds, matching the parser's grammar. Higher binds tighter.
+const AND_LEVEL: u8 = 1;
+const POW_LEVEL: u8 = 7;
+const POSTFIX_LEVEL: u8 = 8;
+const KEYWORD_LEVEL: u8 = 9;
+const NEGATE_LEVEL: u8 = 10;
+const ATOM_LEVEL: u8 = 11;
+
+fn binary_level(op: Op) -> u8
+{
+    match op
+    {
+        Op::And => AND_LEVEL,
+        Op::Or => 2,
+        Op::Equal | Op::NotEqual => 3,
+        Op::Lesser | Op::Greater | Op::LesserEq | Op::GreaterEq => 4,
+        Op::Add | Op::Sub => 5,
+        Op::Mul | Op::Div | Op::Mod => 6,
+        _ => POW_LEVEL
+    }
+}
+
+fn expression_level(expr: &Expr) -> u8
+{
+    match expr
+    {
+        Expr::BinaryOp(op, _, _) => binary_level(*op),
+        Expr::UnaryOp(Op::Fact, _) => POSTFIX_LEVEL,
+        Expr::UnaryOp(Op::Negate, _) => NEGATE_LEVEL,
+        Expr::UnaryOp(Op::PreInc | Op::PostInc | Op::PreDec | Op::PostDec, _) => ATOM_LEVEL,
+        Expr::UnaryOp(_, _) => KEYWORD_LEVEL,
+        // Negative numbers are written with a minus, so they bind like a negation
+        Expr::Value(Value::NumberVal(num)) if num.to_string().starts_with('-') => NEGATE_LEVEL,
+        Expr::Value(_) => ATOM_LEVEL
+    }
+}
+
+impl Formatter
+{
+    pub fn new(style: FormatStyle) -> Formatter
+    {
+        Formatter {
+            style
+        }
+    }
+
+    /// Formats every line of the program, each ending with a newline
+    pub fn format_program(&self, program: &Program) -> String
+    {
+        program.0.iter()
+            .map(|line| self.format_line(line) + "\n")
+            .collect()
+    }
+
+    pub fn format_line(&self, line: &Line) -> String
+    {
+        let mut tokens = Vec::new();
+        self.push_statements(&line.0, &mut tokens);
+
+        self.join(&tokens)
+    }
+
+    pub fn format_statement(&self, statement: &Stat) -> String
+    {
+        self.join(&self.statement_tokens(statement))
+    }
+
+    pub fn format_expression(&self, expr: &Expr) -> String
+    {
+        self.join(&self.expression_tokens(expr, 0))
+    }
+
+    fn keyword(&self, keyword: &str) -> Tok
+    {
+        let text = match self.style.keyword_case
+        {
+            KeywordCase::Lower => keyword.to_ascii_lowercase(),
+            KeywordCase::Upper => keyword.to_ascii_uppercase()
+        };
+
+        Tok::new(text, TokKind::Keyword)
+    }
+
+    fn join(&self, tokens: &[Tok]) -> String
+    {
+        let mut output = String::new();
+        let mut previous: Option<&Tok> = None;
+
+        for token in tokens
+        {
+            if let Some(previous) = previous
+            {
+                if self.needs_space(previous, token)
+                {
+                    output.push(' ');
+                }
+            }
+
+            output += &token.text;
+            previous = Some(token);
+        }
+
+        output
+    }
+
+    fn needs_space(&self, previous: &Tok, next: &Tok) -> bool
+    {
+        // Names, numbers and keywords would run together into one token
+        let word_char = |c: char| c.is_ascii_alphanumeric() || c == '_' || c == ':' || c == '.';
+        let runs_together = previous.text.ends_with(word_char) && next.text.starts_with(word_char);
+
+        match self.style.spacing
+        {
+            Spacing::Compact => runs_together || next.kind == TokKind::Comment,
+            Spacing::Normal => {
+                let glued = matches!(previous.kind, TokKind::Prefix | TokKind::Open) ||
+                    matches!(next.kind, TokKind::Postfix | TokKind::Close);
+
+                runs_together || !glued
+            }
+        }
+    }
+
+    // Adds statements one after another, keeping an expression statement from being read as part of the one before it
+    fn push_statements(&self, statements: &[Spanned<Stat>], tokens: &mut Vec<Tok>)
+    {
+        for statement in statements.iter().map(|statement| &statement.node)
+        {
+            let mut next = self.statement_tokens(statement);
+
+            let continues_expression = match tokens.last()
+            {
+                Some(last) => !matches!(last.kind, TokKind::Keyword | TokKind::Comment),
+                None => false
+            };
+
+            let starts_with_sign = next.first().is_some_and(|first| first.text.starts_with(['-', '+']));
+
+            if continues_expression && starts_with_sign
+            {
+                if let Stat::Expression(expr) = statement
+                {
+                    next = parenthesize(self.expression_tokens(expr, 0));
+                }
+            }
+
+            tokens.extend(next);
+        }
+    }
+
+    fn statement_tokens(&self, statement: &Stat) -> Vec<Tok>
+    {
+        let mut tokens = Vec::new();
+
+        match statement
+        {
+            Stat::Comment(comment) => tokens.push(Tok::new(format!("//{}", comment), TokKind::Comment)),
+            Stat::If(cond, body, else_body) => {
+                tokens.push(self.keyword("if"));
+                tokens.extend(self.expression_tokens(cond, 0));
+                tokens.push(self.keyword("then"));
+
+                self.push_statements(body, &mut tokens);
+
+                if let Some(else_body) = else_body.as_ref().filter(|else_body| !else_body.is_empty())
+                {
+                    tokens.push(self.keyword("else"));
+                    self.push_statements(else_body, &mut tokens);
+                }
+
+                tokens.push(self.keyword("end"));
+            },
+            Stat::Goto(target) => {
+                tokens.push(self.keyword("goto"));
+                tokens.extend(self.expression_tokens(target, 0));
+            },
+            Stat::Assignment(ident, op, expr) => {
+                tokens.extend(self.value_tokens(ident, 0));
+                tokens.push(Tok::new(op.to_string(), TokKind::Op));
+                tokens.extend(self.expression_tokens(expr, 0));
+            },
+            Stat::Expression(expr) => tokens.extend(self.expression_tokens(expr, 0))
+        }
+
+        tokens
+    }
+
+    // Renders the expression where it has to bind at least as tightly as `min_level`, adding parentheses if it doesn't
+    fn expression_tokens(&self, expr: &Expr, min_level: u8) -> Vec<Tok>
+    {
+        if let Expr::Value(value) = expr
+        {
+            return self.value_tokens(value, min_level);
+        }
+
+        let tokens = match expr
+        {
+            Expr::BinaryOp(op, left, right) => self.binary_tokens(*op, left, right),
+
+            Expr::UnaryOp(op @ (Op::PostInc | Op::PostDec | Op::Fact), operand) => {
+                let operand_level = if *op == Op::Fact { POSTFIX_LEVEL } else { ATOM_LEVEL };
+
+                let mut tokens = self.expression_tokens(operand, operand_level);
+                tokens.push(Tok::new(op.to_string(), TokKind::Postfix));
+                tokens
+            },
+            Expr::UnaryOp(op @ (Op::PreInc | Op::PreDec), operand) => {
+                let mut tokens = vec![Tok::new(op.to_string(), TokKind::Prefix)];
+                tokens.extend(self.expression_tokens(operand, ATOM_LEVEL));
+                tokens
+            },
+            Expr::UnaryOp(Op::Negate, operand) => {
+                let mut operand = self.expression_tokens(operand, NEGATE_LEVEL);
+
+                // `- -a` would be read as `--a`, a decrement
+                if let [first, second, ..] = operand.as_slice()
+                {
+                    if first.text == "-" && second.kind == TokKind::Ident
+                    {
+                        operand = parenthesize(operand);
+                    }
+                }
+
+                let mut tokens = vec![Tok::new("-", TokKind::Prefix)];
+                tokens.extend(operand);
+                tokens
+            },
+            Expr::UnaryOp(op, operand) => {
+                let mut tokens = vec![self.keyword(&op.to_string())];
+                tokens.extend(self.expression_tokens(operand, KEYWORD_LEVEL));
+                tokens
+            },
+
+            Expr::Value(_) => unreachable!()
+        };
+
+        if expression_level(expr) < min_level
+        {
+            parenthesize(tokens)
+        }
+        else
+        {
+            tokens
+        }
+    }
+
+    fn binary_tokens(&self, op: Op, left: &Expr, right: &Expr) -> Vec<Tok>
+    {
+        let level = binary_level(op);
+
+        // Everything is left associative except for exponents
+        let (left_level, right_level) = if op == Op::Pow
+        {
+            (POSTFIX_LEVEL, POW_LEVEL)
+        }
+        else
+        {
+            (level, level + 1)
+        };
+
+        let mut left = self.expression_tokens(left, left_level);
+        let mut right = self.expression_tokens(right, right_level);
+
+        // `a! == b` would be read as `a != = b`
+        if op == Op::Equal && left.last().is_some_and(|last| last.text == "!")
+        {
+            left = parenthesize(left);
+        }
+
+        // `a - -b` and `a + ++b` would be read as `a-- - b` and `a++ + b`
+        let op_text = op.to_string();
+        let after_ident = left.last().is_some_and(|last| last.kind == TokKind::Ident);
+        if after_ident && (op == Op::Add || op == Op::Sub) && right.first().is_some_and(|first| first.text.starts_with(&op_text))
+        {
+            right = parenthesize(right);
+        }
+
+        let op_token = match op
+        {
+            Op::And | Op::Or => self.keyword(&op_text),
+            _ => Tok::new(op_text, TokKind::Op)
+        };
+
+        left.push(op_token);
+        left.extend(right);
+        left
+    }
+
+    fn value_tokens(&self, value: &Value, min_level: u8) -> Vec<Tok>
+    {
+        match value
+        {
+            Value::Group(expr) => match self.style.parens
+            {
+                ParenPolicy::Preserve => parenthesize(self.expression_tokens(expr, 0)),
+                // The group disappears, so its contents get parenthesized again only if they need it
+                ParenPolicy::Minimal => self.expression_tokens(expr, min_level)
+            },
+            Value::LocalVar(ident) |
+            Value::DataField(ident) => vec![Tok::new(ident.name.clone(), TokKind::Ident)],
+            Value::NumberVal(num) => {
+                let tokens = vec![Tok::new(num.to_string(), TokKind::Literal)];
+                if expression_level(&Expr::Value(value.clone())) < min_level
+                {
+                    parenthesize(tokens)
+                }
+                else
+                {
+                    tokens
+                }
+            },
+            Value::StringVal(string) => vec![Tok::new(format!("\"{}\"", string), TokKind::Literal)]
+        }
+    }
+}
+
+fn parenthesize(tokens: Vec<Tok>) -> Vec<Tok>
+{
+    let mut output = vec![Tok::new("(", TokKind::Open)];
+    output.extend(tokens);
+    output.push(Tok::new(")", TokKind::Close));
+
+    output
+}
+
+/// Formats the program in the standard style
+pub fn format_program(program: &Program) -> String
+{
+    Formatter::default().format_program(program)
+}
+
+/// Whether two programs have the same code, ignoring where it came from in the source
+pub fn same_code(left: &Program, right: &Program) -> bool
+{
+    left.0.len() == right.0.len() &&
+        left.0.iter().zip(&right.0).all(|(left, right)| {
+            left.0.len() == right.0.len() &&
+                left.0.iter().zip(&right.0).all(|(left, right)| left.node == right.node)
+        })
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    use crate::chip::Chip;
+    use crate::types::TokenWindow;
+    use crate::tokenizer;
+    use crate::parser;
+
+    // Sets the variables the cases use to values where getting the grouping wrong changes the result
+    const SETUP: &str = "a = 0 b = 3 c = 2 d = 1 e = 5\n";
+
+    const CASES: [&str; 10] = [
+        "a = (-b) ^ 2",
+        "a = -b ^ 2",
+        "a = -(b ^ 2)",
+        "a = not not b",
+        "a = not (not b and c)",
+        "a = b < c < d",
+        "a = b == c != d",
+        "a = (b < c) == (d > e) - (b - (c - d))",
+        "if a then b = 1 else if c then d = 2 else e = 3 end end",
+        "if not a == b then c = (a + b) * -c! else goto 1 end"
+    ];
+
+    fn parse(source: &str) -> Program
+    {
+        let tokens = tokenizer::tokenize(String::from(source)).unwrap();
+        parser::parse_program(&mut TokenWindow::from(tokens)).unwrap()
+    }
+
+    fn styles() -> Vec<FormatStyle>
+    {
+        let mut styles = Vec::new();
+
+        for spacing in [Spacing::Normal, Spacing::Compact]
+        {
+            for keyword_case in [KeywordCase::Lower, KeywordCase::Upper]
+            {
+                for parens in [ParenPolicy::Preserve, ParenPolicy::Minimal]
+                {
+                    styles.push(FormatStyle { spacing, keyword_case, parens });
+                }
+            }
+        }
+
+        styles
+    }
+
+    fn run(program: Program) -> Vec<(String, String)>
+    {
+        let mut chip = Chip::new("test", program);
+        chip.run_ticks(2).unwrap();
+
+        chip.env.local_context()
+            .map(|(name, value)| (String::from(name), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn preserved_parens_parse_back_to_the_same_program()
+    {
+        for case in CASES
+        {
+            let program = parse(case);
+
+            for style in styles().into_iter().filter(|style| style.parens == ParenPolicy::Preserve)
+            {
+                let formatted = Formatter::new(style).format_program(&program);
+                assert!(same_code(&parse(&formatted), &program), "{:?} formatted {:?} as {:?}", style, case, formatted);
+            }
+        }
+    }
+
+    #[test]
+    fn minimal_parens_keep_what_the_program_does()
+    {
+        for case in CASES
+        {
+            let source = format!("{}{}\n", SETUP, case);
+            let program = parse(&source);
+
+            for style in styles().into_iter().filter(|style| style.parens == ParenPolicy::Minimal)
+            {
+                let formatter = Formatter::new(style);
+                let formatted = formatter.format_program(&program);
+                let reparsed = parse(&formatted);
+
+                assert_eq!(formatter.format_program(&reparsed), formatted, "{:?}", style);
+                assert_eq!(run(reparsed), run(program.clone()), "{:?} formatted {:?} as {:?}", style, case, formatted);
+            }
+        }
+    }
+
+    #[test]
+    fn minimal_parens_keep_needed_groups()
+    {
+        let style = FormatStyle { parens: ParenPolicy::Minimal, ..FormatStyle::default() };
+        let formatted = Formatter::new(style).format_program(&parse("a = -(b ^ 2) g = (-b) ^ 2 c = (d + e) * (f)\n"));
+
+        // Negation binds tighter than powers, so only the first group is needed
+        assert_eq!(formatted, "a = -(b ^ 2) g = -b ^ 2 c = (d + e) * f\n");
+    }
+}
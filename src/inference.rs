@@ -0,0 +1,706 @@
+use std::fmt;
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+
+use serde::Serialize;
+
+use crate::types::ast::{
+    statement::Statement as Stat,
+    expression::Expression as Expr,
+    operators::Operator as Op,
+    value::{Value, LiteralValue},
+    line::Line,
+    program::Program
+};
+
+use crate::types::{ChipLimits, Ident, Span, Spanned};
+
+use crate::environment::normalize_ident;
+
+use crate::lint::{Lint, LintKind};
+use crate::formatter::Formatter;
+use crate::optimizer;
+
+/// The type a variable can have at some point in a program
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ValueType
+{
+    Number,
+    String,
+    /// Could be either, depending on the path taken to get there
+    Either
+}
+
+impl ValueType
+{
+    /// The type of a value that came from either of two paths
+    pub fn join(self, other: ValueType) -> ValueType
+    {
+        if self == other { self } else { ValueType::Either }
+    }
+}
+
+impl fmt::Display for ValueType
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+    {
+        match self
+        {
+            ValueType::Number => write!(f, "number"),
+            ValueType::String => write!(f, "string"),
+            ValueType::Either => write!(f, "number or string")
+        }
+    }
+}
+
+/// The type of every variable at one point in a program.
+/// Locals that haven't been written are numbers, since they start as 0. Data fields can be set by devices
+/// between lines, so they're only known from when the line writes them.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct TypeEnv
+{
+    locals: BTreeMap<String, ValueType>,
+    fields: BTreeMap<String, ValueType>,
+    /// Locals that have been written on every path to this point
+    written: BTreeSet<String>
+}
+
+impl TypeEnv
+{
+    pub fn get(&self, ident: &Ident) -> ValueType
+    {
+        self.get_key(&ident.key)
+    }
+
+    /// Looks up a variable by name, so names the program never uses can be asked about too
+    pub fn get_name(&self, name: &str) -> ValueType
+    {
+        self.get_key(&normalize_ident(name))
+    }
+
+    pub fn set(&mut self, ident: &Ident, value_type: ValueType)
+    {
+        if ident.is_data_field()
+        {
+            self.fields.insert(ident.key.clone(), value_type);
+        }
+        else
+        {
+            self.locals.insert(ident.key.clone(), value_type);
+            self.written.insert(ident.key.clone());
+        }
+    }
+
+    /// Whether the local has been written on every path to this point, so reading it can't give its starting 0
+    pub fn is_written(&self, ident: &Ident) -> bool
+    {
+        self.written.contains(&ident.key)
+    }
+
+    /// The types of every variable the program has written by this point, by normalized name
+    pub fn variables(&self) -> BTreeMap<String, ValueType>
+    {
+        self.locals.iter().chain(&self.fields)
+            .map(|(name, value_type)| (name.clone(), *value_type))
+            .collect()
+    }
+
+    /// The types at a point reached from both environments
+    pub fn join(&self, other: &TypeEnv) -> TypeEnv
+    {
+        let join_map = |left: &BTreeMap<String, ValueType>, right: &BTreeMap<String, ValueType>, default: ValueType| {
+            left.keys().chain(right.keys())
+                .map(|name| {
+                    let left = left.get(name).copied().unwrap_or(default);
+                    let right = right.get(name).copied().unwrap_or(default);
+                    (name.clone(), left.join(right))
+                })
+                .collect()
+        };
+
+        TypeEnv {
+            locals: join_map(&self.locals, &other.locals, ValueType::Number),
+            fields: join_map(&self.fields, &other.fields, ValueType::Either),
+            written: self.written.intersection(&other.written).cloned().collect()
+        }
+    }
+
+    fn get_key(&self, key: &str) -> ValueType
+    {
+        if key.starts_with(':')
+        {
+            self.fields.get(key).copied().unwrap_or(ValueType::Either)
+        }
+        else
+        {
+            self.locals.get(key).copied().unwrap_or(ValueType::Number)
+        }
+    }
+
+    fn begin_line(&mut self)
+    {
+        self.fields.clear();
+    }
+}
+
+/// The types inferred for a program, at the start of each line and before each statement
+#[derive(Debug, Clone, Default)]
+pub struct TypeInfo
+{
+    lines: Vec<Option<TypeEnv>>,
+    statements: Vec<Vec<Option<TypeEnv>>>,
+    /// The spelling each variable first has in the program, by normalized name
+    names: BTreeMap<String, String>
+}
+
+impl TypeInfo
+{
+    /// The types at the start of the line, or None if the line is never reached.
+    /// Lines are numbered from 1.
+    pub fn line_entry(&self, line: usize) -> Option<&TypeEnv>
+    {
+        self.lines.get(line.checked_sub(1)?)?.as_ref()
+    }
+
+    /// The types just before the statement at the index runs, or None if it's never reached
+    pub fn before_statement(&self, line: usize, statement: usize) -> Option<&TypeEnv>
+    {
+        self.statements.get(line.checked_sub(1)?)?.get(statement)?.as_ref()
+    }
+
+    /// The spelling the program first uses for a normalized name, like the ones `TypeEnv::variables` gives
+    pub fn name<'a>(&'a self, key: &'a str) -> &'a str
+    {
+        self.names.get(key).map_or(key, String::as_str)
+    }
+}
+
+// Where execution goes at the end of a line
+#[derive(Debug, Clone, Copy)]
+enum Target
+{
+    Next,
+    Line(usize),
+    /// A goto to a line that's only known when the program runs
+    Any
+}
+
+/// Works out the type of every variable at every point in the program.
+///
+/// Runtime errors are assumed to skip to the next line, like they do in game, so the types after a line that
+/// can fail include the types from the point it could have failed at.
+pub fn infer(program: &Program, limits: &ChipLimits) -> TypeInfo
+{
+    let line_count = program.0.len();
+    let mut lines: Vec<Option<TypeEnv>> = vec![None; line_count];
+
+    if line_count == 0
+    {
+        return TypeInfo::default();
+    }
+
+    lines[0] = Some(TypeEnv::default());
+    let mut queue = VecDeque::from(vec![0]);
+
+    while let Some(index) = queue.pop_front()
+    {
+        let env = match &lines[index]
+        {
+            Some(env) => env.clone(),
+            None => continue
+        };
+
+        let mut flow = Flow::new(limits, None);
+        flow.line(&program.0[index], env);
+
+        for (target, mut env) in flow.exits
+        {
+            env.begin_line();
+            let targets = match target
+            {
+                Target::Next => vec![index + 1],
+                Target::Line(line) => vec![line],
+                Target::Any => (0..line_count).collect()
+            };
+
+            for target in targets
+            {
+                // Lines past the end of the program are empty, so execution carries on from line 1
+                let target = if target < line_count { target } else { 0 };
+
+                let joined = match &lines[target]
+                {
+                    Some(existing) => existing.join(&env),
+                    None => env.clone()
+                };
+
+                if lines[target].as_ref() != Some(&joined)
+                {
+                    lines[target] = Some(joined);
+                    queue.push_back(target);
+                }
+            }
+        }
+    }
+
+    let statements = program.0.iter().zip(&lines)
+        .map(|(line, entry)| match entry
+        {
+            Some(env) => {
+                let mut flow = Flow::new(limits, None);
+                flow.line(line, env.clone());
+                flow.statements
+            },
+            None => vec![None; line.0.len()]
+        })
+        .collect();
+
+    let mut names = BTreeMap::new();
+    for statement in program.0.iter().flat_map(|line| &line.0)
+    {
+        collect_names(statement, &mut names);
+    }
+
+    TypeInfo {
+        lines,
+        statements,
+        names
+    }
+}
+
+/// Finds operations that are always given a string they can't work with, on every path that reaches them,
+/// and reads of locals that haven't been written yet on some path that reaches them
+pub fn flow_lints(program: &Program, info: &TypeInfo, limits: &ChipLimits) -> Vec<Lint>
+{
+    let mut lints = Vec::new();
+
+    for (line, entry) in program.0.iter().zip(&info.lines)
+    {
+        if let Some(env) = entry
+        {
+            let mut flow = Flow::new(limits, Some(&mut lints));
+            flow.line(line, env.clone());
+        }
+    }
+
+    lints
+}
+
+/// The type an expression evaluates to with the given variable types
+pub fn expression_type(expr: &Spanned<Expr>, env: &TypeEnv) -> ValueType
+{
+    Flow::new(&ChipLimits::unlimited(), None).expression(expr, env)
+}
+
+// Runs a line over types instead of values
+struct Flow<'a>
+{
+    limits: &'a ChipLimits,
+    /// Where the line can go next, and the types it goes there with
+    exits: Vec<(Target, TypeEnv)>,
+    /// The types before each top level statement of the line
+    statements: Vec<Option<TypeEnv>>,
+
+    lints: Option<&'a mut Vec<Lint>>
+}
+
+impl<'a> Flow<'a>
+{
+    fn new(limits: &'a ChipLimits, lints: Option<&'a mut Vec<Lint>>) -> Flow<'a>
+    {
+        Flow {
+            limits,
+            exits: Vec::new(),
+            statements: Vec::new(),
+            lints
+        }
+    }
+
+    fn line(&mut self, line: &Line, mut env: TypeEnv)
+    {
+        env.begin_line();
+        let mut env = Some(env);
+
+        for statement in &line.0
+        {
+            self.statements.push(env.clone());
+
+            env = match env
+            {
+                Some(env) => self.statement(statement, env),
+                None => None
+            };
+        }
+
+        if let Some(env) = env
+        {
+            self.exits.push((Target::Next, env));
+        }
+    }
+
+    // Returns the types after the statement, or None if it always jumps away
+    fn statement(&mut self, statement: &Spanned<Stat>, mut env: TypeEnv) -> Option<TypeEnv>
+    {
+        match &statement.node
+        {
+            Stat::Comment(_) => Some(env),
+            Stat::If(cond, body, else_body) => {
+                self.expression(cond, &env);
+                self.fail_if(can_fail(cond), &env);
+                mark_increments(cond, &mut env);
+
+                let after_body = self.body(body, env.clone());
+                let after_else = match else_body
+                {
+                    Some(else_body) => self.body(else_body, env),
+                    None => Some(env)
+                };
+
+                match (after_body, after_else)
+                {
+                    (Some(left), Some(right)) => Some(left.join(&right)),
+                    (left, right) => left.or(right)
+                }
+            },
+            Stat::Goto(expr) => {
+                let target_type = self.expression(expr, &env);
+                self.fail_if(can_fail(expr), &env);
+                mark_increments(expr, &mut env);
+
+                match optimizer::constant_value(&optimizer::optimize_expression(expr))
+                {
+                    Some(LiteralValue::NumberVal(num)) => {
+                        // Same as the interpreter, the line is rounded down and clamped to the chip
+                        let line: i64 = num.floor().clamp(1, self.limits.last_line()).get_value();
+                        self.exits.push((Target::Line(line as usize - 1), env));
+                    },
+                    _ => {
+                        if target_type == ValueType::String
+                        {
+                            self.mismatch(Formatter::default().format_statement(statement), statement.span);
+                        }
+
+                        // A string target is an error, which moves on to the next line
+                        if target_type != ValueType::Number
+                        {
+                            self.exits.push((Target::Next, env.clone()));
+                        }
+                        if target_type != ValueType::String
+                        {
+                            self.exits.push((Target::Any, env));
+                        }
+                    }
+                }
+
+                None
+            },
+            Stat::Assignment(target, op, expr) => {
+                let value_type = self.expression(expr, &env);
+
+                let ident = match target
+                {
+                    Value::LocalVar(ident) |
+                    Value::DataField(ident) => ident,
+                    _ => return Some(env)
+                };
+
+                let current_type = env.get(ident);
+                let (new_type, fails) = match op
+                {
+                    Op::Assign => (value_type, false),
+                    Op::AddAssign | Op::SubAssign => (add_type(current_type, value_type), false),
+                    _ => {
+                        if current_type == ValueType::String || value_type == ValueType::String
+                        {
+                            self.mismatch(Formatter::default().format_statement(statement), statement.span);
+                        }
+
+                        (ValueType::Number, true)
+                    }
+                };
+
+                self.fail_if(fails || can_fail(expr), &env);
+                mark_increments(expr, &mut env);
+
+                env.set(ident, new_type);
+                Some(env)
+            },
+            Stat::Expression(expr) => {
+                // Incrementing or decrementing a local on its own doesn't count as reading it, it's how counters start
+                let counts = matches!(&expr.node, Expr::UnaryOp(Op::PreInc | Op::PostInc | Op::PreDec | Op::PostDec, operand)
+                    if matches!(operand.node, Expr::Value(Value::LocalVar(_))));
+
+                if !counts
+                {
+                    self.expression(expr, &env);
+                }
+
+                self.fail_if(can_fail(expr), &env);
+                mark_increments(expr, &mut env);
+
+                Some(env)
+            }
+        }
+    }
+
+    fn body(&mut self, body: &[Spanned<Stat>], env: TypeEnv) -> Option<TypeEnv>
+    {
+        let mut env = Some(env);
+
+        for statement in body
+        {
+            env = self.statement(statement, env?);
+        }
+
+        env
+    }
+
+    // An error skips the rest of the line, with the types from before the statement
+    fn fail_if(&mut self, fails: bool, env: &TypeEnv)
+    {
+        if fails
+        {
+            self.exits.push((Target::Next, env.clone()));
+        }
+    }
+
+    fn expression(&mut self, expr: &Spanned<Expr>, env: &TypeEnv) -> ValueType
+    {
+        match &expr.node
+        {
+            Expr::BinaryOp(op, left, right) => {
+                let left = self.expression(left, env);
+                let right = self.expression(right, env);
+
+                match op
+                {
+                    Op::Add | Op::Sub => add_type(left, right),
+                    Op::Mul | Op::Div | Op::Mod | Op::Pow => {
+                        if left == ValueType::String || right == ValueType::String
+                        {
+                            self.mismatch(Formatter::default().format_expression(expr), expr.span);
+                        }
+
+                        ValueType::Number
+                    },
+                    _ => ValueType::Number
+                }
+            },
+            // Incrementing or decrementing doesn't change the type of a variable
+            Expr::UnaryOp(Op::PreInc | Op::PostInc | Op::PreDec | Op::PostDec, operand) => self.expression(operand, env),
+            Expr::UnaryOp(Op::Not, operand) => {
+                self.expression(operand, env);
+                ValueType::Number
+            },
+            Expr::UnaryOp(_, operand) => {
+                if self.expression(operand, env) == ValueType::String
+                {
+                    self.mismatch(Formatter::default().format_expression(expr), expr.span);
+                }
+
+                ValueType::Number
+            },
+            Expr::Value(Value::Group(inner)) => self.expression(inner, env),
+            Expr::Value(Value::LocalVar(ident)) => {
+                if !env.is_written(ident)
+                {
+                    self.lint(LintKind::ReadBeforeWrite { name: ident.name.clone() }, expr.span);
+                }
+
+                env.get(ident)
+            },
+            Expr::Value(Value::DataField(ident)) => env.get(ident),
+            Expr::Value(Value::NumberVal(_)) => ValueType::Number,
+            Expr::Value(Value::StringVal(_)) => ValueType::String
+        }
+    }
+
+    fn mismatch(&mut self, source: String, span: Span)
+    {
+        self.lint(LintKind::TypeMismatch { source }, span);
+    }
+
+    fn lint(&mut self, kind: LintKind, span: Span)
+    {
+        if let Some(lints) = self.lints.as_deref_mut()
+        {
+            lints.push(Lint { kind, span });
+        }
+    }
+}
+
+// Incrementing or decrementing a local writes it, without changing its type
+fn mark_increments(expr: &Expr, env: &mut TypeEnv)
+{
+    match expr
+    {
+        Expr::BinaryOp(_, left, right) => {
+            mark_increments(left, env);
+            mark_increments(right, env);
+        },
+        Expr::UnaryOp(Op::PreInc | Op::PostInc | Op::PreDec | Op::PostDec, operand) => match &operand.node
+        {
+            Expr::Value(Value::LocalVar(ident)) => {
+                env.written.insert(ident.key.clone());
+            },
+            _ => mark_increments(operand, env)
+        },
+        Expr::UnaryOp(_, operand) => mark_increments(operand, env),
+        Expr::Value(Value::Group(inner)) => mark_increments(inner, env),
+        Expr::Value(_) => {}
+    }
+}
+
+// Remembers the spelling of every variable the statement uses, unless an earlier one used it already
+fn collect_names(statement: &Stat, names: &mut BTreeMap<String, String>)
+{
+    match statement
+    {
+        Stat::Comment(_) => {},
+        Stat::If(cond, body, else_body) => {
+            collect_expression_names(cond, names);
+            for statement in body.iter().chain(else_body.iter().flatten())
+            {
+                collect_names(statement, names);
+            }
+        },
+        Stat::Goto(expr) | Stat::Expression(expr) => collect_expression_names(expr, names),
+        Stat::Assignment(target, _, expr) => {
+            if let Value::LocalVar(ident) | Value::DataField(ident) = target
+            {
+                names.entry(ident.key.clone()).or_insert_with(|| ident.name.clone());
+            }
+            collect_expression_names(expr, names);
+        }
+    }
+}
+
+fn collect_expression_names(expr: &Expr, names: &mut BTreeMap<String, String>)
+{
+    match expr
+    {
+        Expr::BinaryOp(_, left, right) => {
+            collect_expression_names(left, names);
+            collect_expression_names(right, names);
+        },
+        Expr::UnaryOp(_, operand) => collect_expression_names(operand, names),
+        Expr::Value(Value::Group(inner)) => collect_expression_names(inner, names),
+        Expr::Value(Value::LocalVar(ident) | Value::DataField(ident)) => {
+            names.entry(ident.key.clone()).or_insert_with(|| ident.name.clone());
+        },
+        Expr::Value(_) => {}
+    }
+}
+
+// Adding or subtracting a string gives a string
+fn add_type(left: ValueType, right: ValueType) -> ValueType
+{
+    match (left, right)
+    {
+        (ValueType::String, _) | (_, ValueType::String) => ValueType::String,
+        (ValueType::Number, ValueType::Number) => ValueType::Number,
+        _ => ValueType::Either
+    }
+}
+
+// Whether evaluating the expression might error, going by its operators alone
+fn can_fail(expr: &Expr) -> bool
+{
+    match expr
+    {
+        Expr::BinaryOp(op, left, right) => {
+            matches!(op, Op::Mul | Op::Div | Op::Mod | Op::Pow) || can_fail(left) || can_fail(right)
+        },
+        // Only decrementing an empty string fails
+        Expr::UnaryOp(Op::PreInc | Op::PostInc | Op::Not, operand) => can_fail(operand),
+        Expr::UnaryOp(_, _) => true,
+        Expr::Value(Value::Group(expr)) => can_fail(expr),
+        Expr::Value(_) => false
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    use crate::types::TokenWindow;
+    use crate::tokenizer;
+    use crate::parser;
+
+    fn infer_source(source: &str) -> TypeInfo
+    {
+        let tokens = tokenizer::tokenize(String::from(source)).unwrap();
+        let program = parser::parse_program(&mut TokenWindow::from(tokens)).unwrap();
+
+        infer(&program, &ChipLimits::default())
+    }
+
+    #[test]
+    fn branches_join_into_either()
+    {
+        let info = infer_source("if :i then a = \"x\" b = \"y\" else b = \"z\" end c = 1\ngoto 2\n");
+        let env = info.line_entry(2).unwrap();
+
+        assert_eq!(env.get_name("a"), ValueType::Either);
+        assert_eq!(env.get_name("b"), ValueType::String);
+        assert_eq!(env.get_name("c"), ValueType::Number);
+    }
+
+    #[test]
+    fn loops_join_what_the_loop_body_writes()
+    {
+        let info = infer_source("a = 1\nb = a a = \"x\" goto 2\n");
+        let env = info.line_entry(2).unwrap();
+
+        assert_eq!(env.get_name("a"), ValueType::Either);
+        assert_eq!(env.get_name("b"), ValueType::Either);
+        assert_eq!(info.line_entry(1).unwrap().get_name("a"), ValueType::Number);
+    }
+
+    #[test]
+    fn wrapping_past_the_last_line_reaches_line_1()
+    {
+        let info = infer_source("a = a + 1\nb = \"x\"\n");
+        assert_eq!(info.line_entry(1).unwrap().get_name("b"), ValueType::Either);
+    }
+
+    #[test]
+    fn errors_reach_the_next_line_without_the_rest_of_theirs()
+    {
+        let info = infer_source("a = \"x\" a = -a b = 1 goto 1\n:o = b goto 2\n");
+        let env = info.line_entry(2).unwrap();
+
+        assert_eq!(env.get_name("a"), ValueType::String);
+        assert!(!env.is_written(&Ident::new("b")));
+    }
+
+    #[test]
+    fn fields_are_forgotten_between_lines()
+    {
+        let info = infer_source(":a = \"x\" b = :a\nc = :a goto 1\n");
+
+        assert_eq!(info.line_entry(2).unwrap().get_name("b"), ValueType::String);
+        assert_eq!(info.line_entry(1).unwrap().get_name("c"), ValueType::Either);
+    }
+
+    #[test]
+    fn only_writes_on_every_path_count_as_written()
+    {
+        let info = infer_source("if :i then a = 1 end b = 1\ngoto 2\n");
+        let env = info.line_entry(2).unwrap();
+
+        assert!(env.is_written(&Ident::new("b")));
+        assert!(!env.is_written(&Ident::new("a")));
+    }
+
+    #[test]
+    fn names_keep_their_first_spelling()
+    {
+        let info = infer_source(":Door = 1 DOOR = :door door = 3\n");
+
+        assert_eq!(info.name(":door"), ":Door");
+        assert_eq!(info.name("door"), "DOOR");
+        assert_eq!(info.name("missing"), "missing");
+    }
+}
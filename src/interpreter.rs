@@ -4,165 +4,273 @@ use crate::types::ast::{
     expression::Expression as Expr,
     operators::Operator as Op,
     operators::OperatorError,
+    operators::OperatorErrorKind,
     value::Value,
     value::LiteralValue,
     line::Line
 };
 
 use crate::types::EvaluationError;
+use crate::types::Ident;
+use crate::types::Spanned;
 use crate::types::EvaluationErrorKind;
 
 use crate::environment::Environment as Env;
-use crate::environment::ContextMap;
+use crate::environment::ErrorMode;
+
+/// What the chip should do after a statement has been evaluated
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ControlFlow
+{
+    /// Carry on with the next statement on the line
+    Continue,
+    /// Skip the rest of the line and jump to the given line number
+    Goto(i64)
+}
 
 pub fn evaluate_line(env: &mut Env, input: &Line) -> Result<(), EvaluationError>
 {
+    let line_number = begin_line(env);
+    let mut result = Ok(());
+
+    for (index, statement) in input.0.iter().enumerate()
+    {
+        match evaluate_line_statement(env, line_number, index, statement)
+        {
+            Ok(true) => {},
+            Ok(false) => break,
+            Err(error) => {
+                result = Err(error);
+                break;
+            }
+        }
+    }
+
+    end_line(env);
+    result
+}
+
+/// Moves the environment on to the line after this one, wrapping at the end of the chip.
+/// Returns the number of the line that's being executed.
+pub fn begin_line(env: &mut Env) -> i64
+{
+    let line_number = env.next_line;
     env.next_line += 1;
 
-    if env.next_line > 20
+    if env.next_line > env.limits.last_line()
     {
         env.next_line = 1;
     }
 
-    for statement in &input.0
+    line_number
+}
+
+/// Evaluates one statement of a line, handling gotos and runtime errors like a whole line would.
+/// Returns whether the rest of the line should still be executed.
+pub fn evaluate_line_statement(env: &mut Env, line_number: i64, index: usize, statement: &Spanned<Stat>) -> Result<bool, EvaluationError>
+{
+    let result = evaluate_statement_ref(env, &statement.node)
+        .map_err(|error| error.with_span(statement.span));
+
+    if let Some(trace) = &mut env.trace
     {
-        match evaluate_statement(env, statement.clone())
+        let goto = match result
         {
-            Err(EvaluationError { kind: EvaluationErrorKind::HitGoto, .. }) => {
-                break;
-            },
-            other => other?
+            Ok(ControlFlow::Goto(line)) => Some(line),
+            _ => None
+        };
+
+        trace.finish_statement(line_number, index, statement.node.to_string(), goto, result.clone().err());
+    }
+
+    match result
+    {
+        Ok(ControlFlow::Continue) => Ok(true),
+        Ok(ControlFlow::Goto(line)) => {
+            env.next_line = line;
+            Ok(false)
+        },
+        Err(error) => {
+            // Any errors that the game wouldn't recover from still get passed up
+            if env.error_mode == ErrorMode::AbortLine && error.is_runtime_error()
+            {
+                env.record_runtime_error(line_number, index, error);
+                return Ok(false);
+            }
+
+            Err(error)
         }
     }
+}
 
+/// Finishes off a line once all of its statements have run
+pub fn end_line(env: &mut Env)
+{
+    if let Some(trace) = &mut env.trace
+    {
+        trace.finish_line();
+    }
+}
 
+// Reads a variable, noting it in the trace if there is one
+fn read_var(env: &mut Env, ident: &Ident) -> LiteralValue
+{
+    let value = env.get(ident);
 
-    Ok(())
+    if let Some(trace) = &mut env.trace
+    {
+        trace.record_read(&ident.name, &value);
+    }
+
+    value
 }
 
-pub fn evaluate_statement(env: &mut Env, input: Stat) -> Result<(), EvaluationError>
+// Writes a variable, noting the old and new values in the trace if there is one
+fn write_var(env: &mut Env, ident: &Ident, value: LiteralValue)
+{
+    if env.trace.is_some()
+    {
+        let old = env.get(ident);
+        if let Some(trace) = &mut env.trace
+        {
+            trace.record_write(&ident.name, old, value.clone());
+        }
+    }
+
+    env.set(ident, value);
+}
+
+/// Takes the statement by value for older callers, prefer `evaluate_statement_ref` which doesn't need a clone
+pub fn evaluate_statement(env: &mut Env, input: Stat) -> Result<ControlFlow, EvaluationError>
+{
+    evaluate_statement_ref(env, &input)
+}
+
+pub fn evaluate_statement_ref(env: &mut Env, input: &Stat) -> Result<ControlFlow, EvaluationError>
 {
     match input
     {
         Stat::Comment(_) => {},
-        Stat::If(cond, body, else_body) => evaluate_if(env, cond, body, else_body)?,
-        Stat::Goto(target) => evaluate_goto(env, target)?,
-        Stat::Assignment(ident, op, expr) => evaluate_assignment(env, ident, op, expr)?,
+        Stat::If(cond, body, else_body) => return evaluate_if(env, cond, body, else_body.as_deref()),
+        Stat::Goto(target) => return evaluate_goto(env, target),
+        Stat::Assignment(ident, op, expr) => evaluate_assignment(env, ident, *op, expr)?,
         Stat::Expression(expr) => { evaluate_expression(env, expr)?; },
     }
 
-    Ok(())
+    Ok(ControlFlow::Continue)
 }
 
-fn evaluate_if(env: &mut Env, cond: Box<Expr>, body: Vec<Stat>, else_body: Option<Vec<Stat>>) -> Result<(), EvaluationError>
+fn evaluate_if(env: &mut Env, cond: &Spanned<Expr>, body: &[Spanned<Stat>], else_body: Option<&[Spanned<Stat>]>) -> Result<ControlFlow, EvaluationError>
 {
     let cond_result = evaluate_expression(env, cond)?;
 
-    if cond_result == LiteralValue::get_false()
+    let statements = if cond_result == LiteralValue::get_false()
     {
-        if let Some(else_body_vec) = else_body
-        {
-            for statement in else_body_vec
-            {
-                evaluate_statement(env, statement)?;
-            }
-        }
+        else_body.unwrap_or_default()
     }
     else
     {
-        for statement in body
+        body
+    };
+
+    for statement in statements
+    {
+        let flow = evaluate_statement_ref(env, &statement.node)
+            .map_err(|error| error.with_span(statement.span))?;
+
+        // A goto in the body jumps out of the if as well as the line
+        if let ControlFlow::Goto(_) = flow
         {
-            evaluate_statement(env, statement)?;
+            return Ok(flow);
         }
     }
 
-    Ok(())
+    Ok(ControlFlow::Continue)
 }
 
-fn evaluate_goto(env: &mut Env, target: Box<Expr>) -> Result<(), EvaluationError>
+fn evaluate_goto(env: &mut Env, target: &Spanned<Expr>) -> Result<ControlFlow, EvaluationError>
 {
     let value = evaluate_expression(env, target)?;
+    Ok(ControlFlow::Goto(goto_line(env, value)?))
+}
 
+/// Turns the value given to a goto into the line it jumps to, clamped to the lines on the chip
+pub(crate) fn goto_line(env: &Env, value: LiteralValue) -> Result<i64, EvaluationError>
+{
     match value
     {
-        LiteralValue::NumberVal(num) => {
-            let num = num.floor();
-            let num = num.clamp(1, 20);
-            env.set_next_line(num);
-        },
-        LiteralValue::StringVal(_) => {
-            return Err(EvaluationError {
-                kind: EvaluationErrorKind::Misc,
-                error_text: String::from("Attempted to goto with a string value!")
-            });
+        LiteralValue::NumberVal(num) => Ok(num.floor().clamp(1, env.limits.last_line()).get_value()),
+        string @ LiteralValue::StringVal(_) => {
+            Err(EvaluationError::new(EvaluationErrorKind::InvalidGotoTarget(string)))
         }
     }
-
-    Err(EvaluationError {
-        kind: EvaluationErrorKind::HitGoto,
-        error_text: String::from("Hit a goto!")
-    })
 }
 
-fn evaluate_assignment(env: &mut Env, ident: Value, op: Op, expr: Box<Expr>) -> Result<(), EvaluationError>
+fn evaluate_assignment(env: &mut Env, ident: &Value, op: Op, expr: &Spanned<Expr>) -> Result<(), EvaluationError>
 {
-    let ident_string = match ident
+    let ident = match ident
     {
-        Value::LocalVar(string) |
-        Value::DataField(string) => string,
+        Value::LocalVar(ident) |
+        Value::DataField(ident) => ident,
 
-        _ => return Err(EvaluationError {
-            kind: EvaluationErrorKind::OperatorError,
-            error_text: String::from("Attempting to use assignment with a non-identifier on the left side!")
-        })
+        other => return Err(EvaluationError::new(EvaluationErrorKind::InvalidAssignmentTarget(other.to_string())))
     };
 
-    let current_value = env.get_val(&ident_string);
-
     let new_value = if let Op::Assign = op
     {
         evaluate_expression(env, expr)?
     }
     else
     {
-        let val = match op
-        {
-            Op::AddAssign => current_value + evaluate_expression(env, expr)?,
-            Op::SubAssign => current_value - evaluate_expression(env, expr)?,
-            Op::MulAssign => current_value * evaluate_expression(env, expr)?,
-            Op::DivAssign => current_value / evaluate_expression(env, expr)?,
-            Op::ModAssign => current_value % evaluate_expression(env, expr)?,
-
-            _ => {
-                return Err(EvaluationError {
-                    kind: EvaluationErrorKind::OperatorError,
-                    error_text: String::from("Attempting to evaluate an assignment without an assignment op!")
-                })
-            }
-        };
-
-        val?
+        let current_value = read_var(env, ident);
+        apply_assign_op(op, current_value, evaluate_expression(env, expr)?)?
     };
 
-    env.set_val(ident_string, new_value);
+    write_var(env, ident, new_value);
     Ok(())
 }
 
-fn evaluate_expression(env: &mut Env, input: Box<Expr>) -> Result<LiteralValue, EvaluationError>
+/// Combines a variable's current value with the value of a compound assignment, like `a += 2`
+pub(crate) fn apply_assign_op(op: Op, current_value: LiteralValue, value: LiteralValue) -> Result<LiteralValue, EvaluationError>
 {
-    match *input
+    let result = match op
     {
-        Expr::BinaryOp(op, left, right) => evaluate_binary_op(env, op, left, right),
-        Expr::UnaryOp(op, target) => evaluate_unary_op(env, op, target),
+        Op::AddAssign => current_value + value,
+        Op::SubAssign => current_value - value,
+        Op::MulAssign => current_value * value,
+        Op::DivAssign => current_value / value,
+        Op::ModAssign => current_value % value,
+        Op::PowAssign => current_value.pow(value),
+
+        _ => return Err(EvaluationError::new(EvaluationErrorKind::UnexpectedOperator(op)))
+    };
+
+    Ok(result?)
+}
+
+// Errors are given the span of the innermost expression that failed
+fn evaluate_expression(env: &mut Env, input: &Spanned<Expr>) -> Result<LiteralValue, EvaluationError>
+{
+    let result = match &input.node
+    {
+        Expr::BinaryOp(op, left, right) => evaluate_binary_op(env, *op, left, right),
+        Expr::UnaryOp(op, target) => evaluate_unary_op(env, *op, target),
         Expr::Value(value) => evaluate_value(env, value),
-    }
+    };
+
+    result.map_err(|error| error.with_span(input.span))
 }
 
-fn evaluate_binary_op(env: &mut Env, op: Op, left: Box<Expr>, right: Box<Expr>) -> Result<LiteralValue, EvaluationError>
+fn evaluate_binary_op(env: &mut Env, op: Op, left: &Spanned<Expr>, right: &Spanned<Expr>) -> Result<LiteralValue, EvaluationError>
 {
     let left_value = evaluate_expression(env, left)?;
     let right_value = evaluate_expression(env, right)?;
 
+    apply_binary_op(op, left_value, right_value)
+}
+
+pub(crate) fn apply_binary_op(op: Op, left_value: LiteralValue, right_value: LiteralValue) -> Result<LiteralValue, EvaluationError>
+{
     if let Op::Lesser | Op::Greater | Op::LesserEq | Op::GreaterEq |
                                 Op::Equal | Op::NotEqual | Op::And | Op::Or = op
     {
@@ -177,10 +285,7 @@ fn evaluate_binary_op(env: &mut Env, op: Op, left: Box<Expr>, right: Box<Expr>)
             Op::And => (left_value != LiteralValue::get_false()) && (right_value != LiteralValue::get_false()),
             Op::Or => (left_value != LiteralValue::get_false()) || (right_value != LiteralValue::get_false()),
 
-            _ => return Err(EvaluationError {
-                kind: EvaluationErrorKind::NonExhaustivePattern,
-                error_text: String::from("Didn't find behaviour to match in boolean section of binary ops")
-            })
+            _ => return Err(EvaluationError::new(EvaluationErrorKind::UnexpectedOperator(op)))
         };
 
         Ok(LiteralValue::from(bool_result))
@@ -196,167 +301,179 @@ fn evaluate_binary_op(env: &mut Env, op: Op, left: Box<Expr>, right: Box<Expr>)
             Op::Mod => left_value % right_value,
             Op::Pow => left_value.pow(right_value),
 
-            _ => return Err(EvaluationError {
-                kind: EvaluationErrorKind::NonExhaustivePattern,
-                error_text: String::from("Didn't find behaviour to match in numerical section of binary ops")
-            })
+            _ => return Err(EvaluationError::new(EvaluationErrorKind::UnexpectedOperator(op)))
         };
 
         Ok(result?)
     }
 }
 
-fn evaluate_unary_op(env: &mut Env, op: Op, target: Box<Expr>) -> Result<LiteralValue, EvaluationError>
+fn evaluate_unary_op(env: &mut Env, op: Op, target: &Spanned<Expr>) -> Result<LiteralValue, EvaluationError>
 {
     if let Op::PreInc | Op::PostInc | Op::PreDec | Op::PostDec = op
     {
-        let ident = match *target
+        let ident = match &target.node
         {
             Expr::Value(Value::LocalVar(ident)) |
             Expr::Value(Value::DataField(ident)) => ident,
-            _ => {
-                return Err(EvaluationError {
-                    kind: EvaluationErrorKind::OperatorError,
-                    error_text: String::from("Tried to use pre/post - inc/dec on a non-identifier!")
-                });
+            other => {
+                return Err(EvaluationError::new(EvaluationErrorKind::InvalidAssignmentTarget(other.to_string())));
             }
         };
 
+        let original_value = read_var(env, ident);
+        let new_value = apply_step_op(op, original_value.clone())?;
+
+        write_var(env, ident, new_value.clone());
+
         match op
         {
-            Op::PreInc => {
-                let new_value = match env.get_val(&ident)
-                {
-                    num @ LiteralValue::NumberVal(_) => num + LiteralValue::from(1),
-                    string @ LiteralValue::StringVal(_) => string + LiteralValue::from(" ")
-                }?;
-
-                env.set_val(ident, new_value.clone());
-                Ok(new_value)
-            },
-            Op::PostInc => {
-                let original_value = env.get_val(&ident);
-                let new_value = match original_value.clone()
-                {
-                    num @ LiteralValue::NumberVal(_) => num + LiteralValue::from(1),
-                    string @ LiteralValue::StringVal(_) => string + LiteralValue::from(" ")
-                };
-
-                env.set_val(ident, new_value?);
-                Ok(original_value)
-            },
-            Op::PreDec => {
-                let new_value = match env.get_val(&ident)
-                {
-                    num @ LiteralValue::NumberVal(_) => num - LiteralValue::from(1),
-                    LiteralValue::StringVal(mut string) => {
-                        if string.pop().is_none()
-                        {
-                            Err(OperatorError {
-                                op: Op::PreDec,
-                                left: Some(LiteralValue::StringVal(string)),
-                                right: None,
-                                error_text: String::from("Tried to use pre-dec on an empty string!")
-                            })
-                        }
-                        else
-                        {
-                            Ok(LiteralValue::StringVal(string))
-                        }
-                    }
-                }?;
-                env.set_val(ident, new_value.clone());
-                Ok(new_value)
-            },
-            Op::PostDec => {
-                let original_value = env.get_val(&ident);
-                let new_value = match original_value.clone()
-                {
-                    num @ LiteralValue::NumberVal(_) => num - LiteralValue::from(1),
-                    LiteralValue::StringVal(mut string) => {
-                        if string.pop().is_none()
-                        {
-                            Err(OperatorError {
-                                op: Op::PreDec,
-                                left: Some(LiteralValue::StringVal(string)),
-                                right: None,
-                                error_text: String::from("Tried to use pre-dec on an empty string!")
-                            })
-                        }
-                        else
-                        {
-                            Ok(LiteralValue::StringVal(string))
-                        }
-                    }
-                }?;
-
-                env.set_val(ident, new_value);
-                Ok(original_value)
-            },
-
-            _ => Err(EvaluationError {
-                kind: EvaluationErrorKind::NonExhaustivePattern,
-                error_text: String::from("Didn't find behaviour to match in pre/post-inc/dec section of unary ops")
-            })
+            Op::PreInc | Op::PreDec => Ok(new_value),
+            _ => Ok(original_value)
         }
     }
     else
     {
         let value = evaluate_expression(env, target)?;
+        apply_unary_op(op, value)
+    }
+}
 
-        if let value @ LiteralValue::NumberVal(_) = value
-        {
-            let result = match op
+/// Works out the new value of a variable being incremented or decremented.
+/// Strings grow by a space and shrink by their last character.
+pub(crate) fn apply_step_op(op: Op, value: LiteralValue) -> Result<LiteralValue, EvaluationError>
+{
+    let result = match (op, value)
+    {
+        (Op::PreInc | Op::PostInc, num @ LiteralValue::NumberVal(_)) => num + LiteralValue::from(1),
+        (Op::PreInc | Op::PostInc, string @ LiteralValue::StringVal(_)) => string + LiteralValue::from(" "),
+        (Op::PreDec | Op::PostDec, num @ LiteralValue::NumberVal(_)) => num - LiteralValue::from(1),
+        (Op::PreDec | Op::PostDec, LiteralValue::StringVal(mut string)) => {
+            if string.pop().is_none()
+            {
+                Err(OperatorError::new(op, Some(LiteralValue::StringVal(string)), None,
+                    OperatorErrorKind::EmptyString))
+            }
+            else
             {
-                Op::Negate => -value,
-                Op::Fact => value.factorial(),
-                
-                Op::Abs => value.abs(),
-                Op::Sqrt => value.sqrt(),
+                Ok(LiteralValue::StringVal(string))
+            }
+        },
 
-                Op::Sin => value.sin(),
-                Op::Cos => value.cos(),
-                Op::Tan => value.tan(),
+        _ => return Err(EvaluationError::new(EvaluationErrorKind::UnexpectedOperator(op)))
+    };
 
-                Op::Arcsin => value.arcsin(),
-                Op::Arccos => value.arccos(),
-                Op::Arctan => value.arctan(),
+    Ok(result?)
+}
 
-                Op::Not => !value,
+/// Operators that don't work on strings give an operator error for them,
+/// which the game recovers from like any other runtime error
+pub(crate) fn apply_unary_op(op: Op, value: LiteralValue) -> Result<LiteralValue, EvaluationError>
+{
+    let result = match op
+    {
+        Op::Negate => -value,
+        Op::Fact => value.factorial(),
+        
+        Op::Abs => value.abs(),
+        Op::Sqrt => value.sqrt(),
 
-                _ => return Err(EvaluationError {
-                    kind: EvaluationErrorKind::NonExhaustivePattern,
-                    error_text: String::from("Didn't find behaviour to match in numerical section of unary ops")
-                })
-            };
+        Op::Sin => value.sin(),
+        Op::Cos => value.cos(),
+        Op::Tan => value.tan(),
 
-            Ok(result?)
-        }
-        else
-        {
-            match op
-            {
-                Op::Not => Ok((!value)?),
+        Op::Arcsin => value.arcsin(),
+        Op::Arccos => value.arccos(),
+        Op::Arctan => value.arctan(),
 
-                _ => Err(EvaluationError {
-                    kind: EvaluationErrorKind::NonExhaustivePattern,
-                    error_text: String::from("Didn't find behaviour to match in final section of binary ops")
-                })
-            }
-        }
-    }
+        Op::Not => !value,
+
+        _ => return Err(EvaluationError::new(EvaluationErrorKind::UnexpectedOperator(op)))
+    };
+
+    Ok(result?)
 }
 
-fn evaluate_value(env: &mut Env, input: Value) -> Result<LiteralValue, EvaluationError>
+fn evaluate_value(env: &mut Env, input: &Value) -> Result<LiteralValue, EvaluationError>
 {
     let output = match input
     {
         Value::Group(expr) => evaluate_expression(env, expr)?,
-        Value::LocalVar(ident) => env.get_val(&ident),
-        Value::DataField(ident) => env.get_val(&ident),
-        Value::NumberVal(number) => LiteralValue::NumberVal(number),
-        Value::StringVal(string) => LiteralValue::StringVal(string),
+        Value::LocalVar(ident) => read_var(env, ident),
+        Value::DataField(ident) => read_var(env, ident),
+        Value::NumberVal(number) => LiteralValue::NumberVal(*number),
+        Value::StringVal(string) => LiteralValue::StringVal(string.clone()),
     };
 
     Ok(output)
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    use crate::chip::Chip;
+    use crate::tokenizer;
+    use crate::parser;
+    use crate::types::TokenWindow;
+    use crate::environment::{ContextMap, ErrorMode};
+
+    fn chip(source: &str) -> Chip
+    {
+        let tokens = tokenizer::tokenize(String::from(source)).unwrap();
+        let program = parser::parse_program(&mut TokenWindow::from(tokens)).unwrap();
+
+        Chip::new("test", program)
+    }
+
+    #[test]
+    fn string_unary_ops_abort_the_line()
+    {
+        for (source, op) in [("-a", Op::Negate), ("abs a", Op::Abs), ("sqrt a", Op::Sqrt), ("sin a", Op::Sin),
+                             ("cos a", Op::Cos), ("tan a", Op::Tan), ("asin a", Op::Arcsin),
+                             ("acos a", Op::Arccos), ("atan a", Op::Arctan)]
+        {
+            let mut chip = chip(&format!("a = \"x\" b = {} c = 1\nd = 2\n", source));
+            chip.env.error_mode = ErrorMode::AbortLine;
+
+            chip.run_ticks(2).unwrap();
+
+            assert_eq!(chip.env.get_val("c"), LiteralValue::from(0), "{}", source);
+            assert_eq!(chip.env.get_val("d"), LiteralValue::from(2), "{}", source);
+
+            assert_eq!(chip.env.runtime_errors.len(), 1, "{}", source);
+            assert!(matches!(&chip.env.runtime_errors[0].error.kind,
+                EvaluationErrorKind::Operator(error) if error.op == op && error.kind == OperatorErrorKind::TypeMismatch), "{}", source);
+        }
+    }
+
+    #[test]
+    fn not_works_on_strings()
+    {
+        let mut chip = chip("a = \"x\" b = not a\n");
+        chip.step().unwrap();
+
+        assert_eq!(chip.env.get_val("b"), LiteralValue::from(0));
+    }
+
+    #[test]
+    fn errors_point_at_the_failing_expression()
+    {
+        let mut chip = chip("a = \"x\" b = 1 + -a\n");
+        let error = chip.step().unwrap_err();
+
+        let span = error.span.unwrap();
+        assert_eq!((span.line, span.column, span.length), (1, 17, 2));
+    }
+
+    #[test]
+    fn pow_assign_raises_the_variable()
+    {
+        let mut chip = chip("a = 3 a ^= 2 b = 2 b ^= -1\n");
+        chip.step().unwrap();
+
+        assert_eq!(chip.env.get_val("a"), LiteralValue::from(9));
+        assert_eq!(chip.env.get_val("b"), LiteralValue::NumberVal("0.5".parse().unwrap()));
+    }
+}
@@ -4,6 +4,22 @@ pub mod environment;
 pub mod parser;
 pub mod tokenizer;
 pub mod interpreter;
+pub mod chip;
+pub mod network;
+pub mod device;
+pub mod timeline;
+pub mod testing;
+pub mod trace;
+pub mod debugger;
+pub mod repl;
+pub mod bytecode;
+pub mod vm;
+pub mod formatter;
+pub mod minifier;
+pub mod optimizer;
+pub mod lint;
+pub mod linter;
+pub mod inference;
 
 pub mod wasm_lib;
 
@@ -21,7 +37,7 @@ pub fn execute_line(env: &mut Environment, line: String)
         }
     };
 
-    let mut window = types::VecWindow::from(tokens);
+    let mut window = types::TokenWindow::from(tokens);
     let line = match parser::parse_line(&mut window)
     {
         Ok(line) => line,
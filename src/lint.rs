@@ -0,0 +1,62 @@
+use std::fmt;
+
+use serde::Serialize;
+
+use crate::types::Span;
+
+/// A likely mistake in a program
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "lint", rename_all = "snake_case")]
+pub enum LintKind
+{
+    /// A local that's read but never written, so it's always 0
+    NeverWritten { name: String },
+    /// A local that's written somewhere, but can be read before it is, when it's still 0
+    ReadBeforeWrite { name: String },
+    /// A local that's written but never read
+    NeverRead { name: String },
+    /// A line that no path through the program reaches
+    UnreachableLine { line: usize },
+    /// Statements after a goto on the same line
+    UnreachableStatement,
+    /// A goto to a constant line that isn't on the chip, which gets clamped to the nearest line that is
+    GotoOutOfRange { target: String, max: usize },
+    /// An operation that fails whatever the values of its variables, because it's given a string
+    TypeMismatch { source: String },
+    LineTooLong { length: usize, max: usize }
+}
+
+impl fmt::Display for LintKind
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+    {
+        match self
+        {
+            LintKind::NeverWritten { name } => write!(f, "'{}' is never written, so it's always 0", name),
+            LintKind::ReadBeforeWrite { name } => write!(f, "'{}' can be read before it's written, when it's still 0", name),
+            LintKind::NeverRead { name } => write!(f, "'{}' is written but never read", name),
+            LintKind::UnreachableLine { line } => write!(f, "line {} can never run", line),
+            LintKind::UnreachableStatement => write!(f, "statements after a goto never run"),
+            LintKind::GotoOutOfRange { target, max } => write!(f, "goto {} is outside lines 1 to {}", target, max),
+            LintKind::TypeMismatch { source } => write!(f, "'{}' always fails, it can't be done on a string", source),
+            LintKind::LineTooLong { length, max } => write!(f, "line is {} characters long, the chip only allows {}", length, max),
+        }
+    }
+}
+
+/// A lint along with where it was found
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Lint
+{
+    #[serde(flatten)]
+    pub kind: LintKind,
+    pub span: Span
+}
+
+impl fmt::Display for Lint
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+    {
+        write!(f, "{}: warning: {}", self.span, self.kind)
+    }
+}
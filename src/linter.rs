@@ -0,0 +1,377 @@
+use crate::types::ast::{
+    statement::Statement as Stat,
+    expression::Expression as Expr,
+    operators::Operator as Op,
+    value::{Value, LiteralValue},
+    program::Program
+};
+
+use crate::types::{ChipLimits, Ident, Span, Spanned};
+
+use crate::environment::normalize_ident;
+use crate::lint::{Lint, LintKind};
+use crate::optimizer;
+use crate::inference::{self, TypeInfo};
+
+/// Checks a program for common mistakes, returning them in the order they appear in the source.
+///
+/// The program should be parsed without limits, so lines that are too long are reported here instead of failing
+/// to parse. Which lines can run comes from type inference, so a runtime error skipping the rest of a line,
+/// goto included, counts as a way to reach the next line.
+pub fn lint(program: &Program, source: &str, limits: &ChipLimits) -> Vec<Lint>
+{
+    let mut lints = Vec::new();
+    let types = inference::infer(program, limits);
+    let flow_lints = inference::flow_lints(program, &types, limits);
+
+    lint_line_lengths(source, limits, &mut lints);
+    lint_variables(program, &flow_lints, &mut lints);
+    lint_control_flow(program, &types, limits, &mut lints);
+    lints.extend(flow_lints.into_iter().filter(|lint| !matches!(lint.kind, LintKind::ReadBeforeWrite { .. })));
+
+    lints.sort_by_key(|lint| (lint.span.line, lint.span.column));
+    lints
+}
+
+fn lint_line_lengths(source: &str, limits: &ChipLimits, lints: &mut Vec<Lint>)
+{
+    let mut offset = 0;
+
+    for (index, line) in source.split('\n').enumerate()
+    {
+        let text = line.strip_suffix('\r').unwrap_or(line);
+
+        let length = text.chars().count();
+        if length > limits.max_line_chars
+        {
+            lints.push(Lint {
+                kind: LintKind::LineTooLong { length, max: limits.max_line_chars },
+                span: Span::new(index + 1, 1, offset, text.len())
+            });
+        }
+
+        offset += line.len() + 1;
+    }
+}
+
+#[derive(Debug, Default)]
+struct Usage
+{
+    name: String,
+    first_read: Option<Span>,
+    first_write: Option<Span>
+}
+
+// Only locals are checked, since data fields are read and written by the devices outside the chip
+// Reads before the first write come from inference, which follows every path from line 1, and only the first one
+// is reported for each local
+fn lint_variables(program: &Program, flow_lints: &[Lint], lints: &mut Vec<Lint>)
+{
+    let mut usages: Vec<(String, Usage)> = Vec::new();
+
+    for statement in program.0.iter().flat_map(|line| &line.0)
+    {
+        visit_statement(statement, &mut |ident, access, span| {
+            let index = match usages.iter().position(|(key, _)| *key == ident.key)
+            {
+                Some(index) => index,
+                None => {
+                    usages.push((ident.key.clone(), Usage { name: ident.name.clone(), ..Usage::default() }));
+                    usages.len() - 1
+                }
+            };
+
+            let usage = &mut usages[index].1;
+            if access.reads
+            {
+                usage.first_read.get_or_insert(span);
+            }
+            if access.writes
+            {
+                usage.first_write.get_or_insert(span);
+            }
+        });
+    }
+
+    for (key, usage) in usages
+    {
+        match (usage.first_read, usage.first_write)
+        {
+            (Some(span), None) => lints.push(Lint { kind: LintKind::NeverWritten { name: usage.name }, span }),
+            (None, Some(span)) => lints.push(Lint { kind: LintKind::NeverRead { name: usage.name }, span }),
+            (Some(_), Some(_)) => {
+                let first = flow_lints.iter()
+                    .filter(|lint| matches!(&lint.kind, LintKind::ReadBeforeWrite { name: read } if normalize_ident(read) == key))
+                    .min_by_key(|lint| (lint.span.line, lint.span.column));
+
+                lints.extend(first.cloned());
+            },
+            (None, None) => {}
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Access
+{
+    reads: bool,
+    writes: bool
+}
+
+const READ: Access = Access { reads: true, writes: false };
+const WRITE: Access = Access { reads: false, writes: true };
+
+// Calls `visit` with every local the statement uses, how it uses it and where.
+// Changing a variable based on its own value, like `a += 1` or `a++` on its own, doesn't count as reading it.
+fn visit_statement(statement: &Spanned<Stat>, visit: &mut impl FnMut(&Ident, Access, Span))
+{
+    match &statement.node
+    {
+        Stat::Comment(_) => {},
+        Stat::If(cond, body, else_body) => {
+            visit_expression(cond, visit);
+            for statement in body.iter().chain(else_body.iter().flatten())
+            {
+                visit_statement(statement, visit);
+            }
+        },
+        Stat::Goto(expr) => visit_expression(expr, visit),
+        Stat::Assignment(target, _, expr) => {
+            visit_expression(expr, visit);
+            if let Value::LocalVar(ident) = target
+            {
+                visit(ident, WRITE, statement.span);
+            }
+        },
+        Stat::Expression(expr) => match &expr.node
+        {
+            Expr::UnaryOp(Op::PreInc | Op::PostInc | Op::PreDec | Op::PostDec, operand) => match &operand.node
+            {
+                Expr::Value(Value::LocalVar(ident)) => visit(ident, WRITE, expr.span),
+                _ => visit_expression(operand, visit)
+            },
+            _ => visit_expression(expr, visit)
+        }
+    }
+}
+
+fn visit_expression(expr: &Spanned<Expr>, visit: &mut impl FnMut(&Ident, Access, Span))
+{
+    match &expr.node
+    {
+        Expr::BinaryOp(_, left, right) => {
+            visit_expression(left, visit);
+            visit_expression(right, visit);
+        },
+        Expr::UnaryOp(Op::PreInc | Op::PostInc | Op::PreDec | Op::PostDec, operand) => match &operand.node
+        {
+            Expr::Value(Value::LocalVar(ident)) => visit(ident, Access { reads: true, writes: true }, expr.span),
+            _ => visit_expression(operand, visit)
+        },
+        Expr::UnaryOp(_, operand) => visit_expression(operand, visit),
+        Expr::Value(Value::Group(inner)) => visit_expression(inner, visit),
+        Expr::Value(Value::LocalVar(ident)) => visit(ident, READ, expr.span),
+        Expr::Value(_) => {}
+    }
+}
+
+// Lines are unreachable when inference never found a way into them
+fn lint_control_flow(program: &Program, types: &TypeInfo, limits: &ChipLimits, lints: &mut Vec<Lint>)
+{
+    for (index, line) in program.0.iter().enumerate()
+    {
+        for (statement_index, statement) in line.0.iter().enumerate()
+        {
+            lint_gotos(statement, limits, lints);
+
+            if let Stat::Goto(_) = &statement.node
+            {
+                let dead: Vec<&Spanned<Stat>> = line.0[statement_index + 1..].iter()
+                    .filter(|statement| !matches!(statement.node, Stat::Comment(_)))
+                    .collect();
+
+                if let (Some(first), Some(last)) = (dead.first(), dead.last())
+                {
+                    lints.push(Lint { kind: LintKind::UnreachableStatement, span: first.span.to(last.span) });
+                }
+
+                break;
+            }
+        }
+
+        let code: Vec<&Spanned<Stat>> = line.0.iter()
+            .filter(|statement| !matches!(statement.node, Stat::Comment(_)))
+            .collect();
+
+        if let (None, Some(first), Some(last)) = (types.line_entry(index + 1), code.first(), code.last())
+        {
+            lints.push(Lint { kind: LintKind::UnreachableLine { line: index + 1 }, span: first.span.to(last.span) });
+        }
+    }
+}
+
+// Warns about gotos to constant lines that aren't on the chip
+fn lint_gotos(statement: &Spanned<Stat>, limits: &ChipLimits, lints: &mut Vec<Lint>)
+{
+    match &statement.node
+    {
+        Stat::If(_, body, else_body) => {
+            for statement in body.iter().chain(else_body.iter().flatten())
+            {
+                lint_gotos(statement, limits, lints);
+            }
+        },
+        Stat::Goto(expr) => {
+            if let Some(LiteralValue::NumberVal(num)) = optimizer::constant_value(&optimizer::optimize_expression(expr))
+            {
+                let line = LiteralValue::NumberVal(num.floor());
+                if line < LiteralValue::from(1) || line > LiteralValue::from(limits.last_line())
+                {
+                    lints.push(Lint {
+                        kind: LintKind::GotoOutOfRange { target: num.to_string(), max: limits.last_line() as usize },
+                        span: statement.span
+                    });
+                }
+            }
+        },
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    use crate::types::TokenWindow;
+    use crate::tokenizer;
+    use crate::parser;
+
+    fn lint_kinds(source: &str) -> Vec<LintKind>
+    {
+        let tokens = tokenizer::tokenize(String::from(source)).unwrap();
+        let program = parser::parse_program(&mut TokenWindow::from(tokens)).unwrap();
+
+        lint(&program, source, &ChipLimits::default()).into_iter()
+            .map(|lint| lint.kind)
+            .collect()
+    }
+
+    #[test]
+    fn unused_locals_are_found()
+    {
+        let kinds = lint_kinds("a = 1 :o = b\n");
+
+        assert!(kinds.contains(&LintKind::NeverRead { name: String::from("a") }));
+        assert!(kinds.contains(&LintKind::NeverWritten { name: String::from("b") }));
+        assert_eq!(kinds.len(), 2);
+    }
+
+    #[test]
+    fn data_fields_are_never_unused()
+    {
+        assert!(lint_kinds(":a = :b goto 1\n").is_empty());
+    }
+
+    #[test]
+    fn statements_after_a_goto_are_unreachable()
+    {
+        let kinds = lint_kinds(":a = 1 goto 1 :b = 2\n");
+        assert_eq!(kinds, vec![LintKind::UnreachableStatement]);
+    }
+
+    #[test]
+    fn constant_gotos_off_the_chip_are_found()
+    {
+        let kinds = lint_kinds("goto 21\n");
+        assert!(kinds.contains(&LintKind::GotoOutOfRange { target: String::from("21"), max: 20 }));
+
+        assert!(lint_kinds("goto 20\n").iter().all(|kind| !matches!(kind, LintKind::GotoOutOfRange { .. })));
+    }
+
+    #[test]
+    fn operations_that_always_fail_on_strings_are_found()
+    {
+        let kinds = lint_kinds(":o = \"x\" * 2 :p = \"x\" + 2 goto 1\n");
+        assert_eq!(kinds, vec![LintKind::TypeMismatch { source: String::from("\"x\" * 2") }]);
+    }
+
+    #[test]
+    fn operations_on_locals_that_are_always_strings_are_found()
+    {
+        let kinds = lint_kinds("a = \"x\" :o = a * 2 :p = a + 2 goto 1\n");
+        assert_eq!(kinds, vec![LintKind::TypeMismatch { source: String::from("a * 2") }]);
+    }
+
+    #[test]
+    fn long_lines_are_found()
+    {
+        let source = format!(":a = \"{}\"\n", "x".repeat(70));
+        assert_eq!(lint_kinds(&source), vec![LintKind::LineTooLong { length: 77, max: 70 }]);
+    }
+
+    #[test]
+    fn lines_after_a_goto_are_unreachable()
+    {
+        let kinds = lint_kinds("a = 1 goto 1\nb = a goto 1\n");
+        assert!(kinds.contains(&LintKind::UnreachableLine { line: 2 }));
+    }
+
+    #[test]
+    fn errors_before_a_goto_reach_the_next_line()
+    {
+        let kinds = lint_kinds("a = \"x\" b = -a goto 1\nc = b goto 1\n");
+        assert!(!kinds.iter().any(|kind| matches!(kind, LintKind::UnreachableLine { .. })));
+    }
+
+    fn reads_before_writes(source: &str) -> Vec<String>
+    {
+        lint_kinds(source).into_iter()
+            .filter_map(|kind| match kind
+            {
+                LintKind::ReadBeforeWrite { name } => Some(name),
+                _ => None
+            })
+            .collect()
+    }
+
+    #[test]
+    fn reads_before_a_later_write_are_found()
+    {
+        assert_eq!(reads_before_writes("b = a + 1 a = 2 :o = b\n"), vec![String::from("a")]);
+        assert_eq!(reads_before_writes(":o = a\na = 1 goto 1\n"), vec![String::from("a")]);
+    }
+
+    #[test]
+    fn reads_after_every_path_writes_are_fine()
+    {
+        assert!(reads_before_writes("a = 1\n:o = a goto 2\n").is_empty());
+        assert!(reads_before_writes("if :i then a = 1 else a = 2 end :o = a\n").is_empty());
+    }
+
+    #[test]
+    fn reads_after_only_one_branch_writes_are_found()
+    {
+        assert_eq!(reads_before_writes("if :i then a = 1 end :o = a\n"), vec![String::from("a")]);
+    }
+
+    #[test]
+    fn reads_after_an_error_skipped_the_write_are_found()
+    {
+        assert_eq!(reads_before_writes("a = 1 / :x b = 1\n:o = b goto 2\n"), vec![String::from("b")]);
+    }
+
+    #[test]
+    fn bare_counters_are_not_reads_before_writes()
+    {
+        assert!(reads_before_writes("a++ :o = a goto 1\n").is_empty());
+    }
+
+    #[test]
+    fn locals_that_are_never_written_are_still_found()
+    {
+        let kinds = lint_kinds(":o = a\n");
+        assert!(kinds.contains(&LintKind::NeverWritten { name: String::from("a") }));
+        assert!(reads_before_writes(":o = a\n").is_empty());
+    }
+}
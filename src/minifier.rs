@@ -0,0 +1,312 @@
+use std::collections::{BTreeMap, HashMap};
+
+use crate::types::ast::{
+    statement::Statement as Stat,
+    expression::Expression as Expr,
+    value::Value,
+    line::Line,
+    program::Program
+};
+
+use crate::types::{Ident, Spanned};
+
+use crate::formatter::{Formatter, FormatStyle, Spacing, KeywordCase, ParenPolicy};
+use crate::optimizer;
+
+/// How long a line was before and after minifying it
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct LineSize
+{
+    /// The length of the line in the source it was parsed from, in characters
+    pub before: usize,
+    pub after: usize
+}
+
+/// A minified program, along with what was done to it
+#[derive(Debug, Clone)]
+pub struct Minified
+{
+    pub program: Program,
+    /// The minified program as source, one line per line of the program
+    pub source: String,
+    pub sizes: Vec<LineSize>,
+    /// The new name of each local variable, by its original normalized name
+    pub renames: BTreeMap<String, String>
+}
+
+const KEYWORDS: &[&str] = &[
+    "if", "then", "else", "end", "goto",
+    "and", "or", "not",
+    "abs", "sqrt", "sin", "cos", "tan", "asin", "acos", "atan"
+];
+
+/// Shrinks a program as much as possible without changing what it does.
+///
+/// Local variables are renamed, with the most used ones getting the shortest names, comments are removed,
+/// constant expressions are folded and the program is printed without any spaces or parentheses it doesn't need.
+/// Data fields keep their names, and every line stays on the same line number so gotos still work.
+/// `source` is the text the program was parsed from, which the sizes of the lines before minifying are measured from.
+pub fn minify(program: &Program, source: &str) -> Minified
+{
+    let renames = rename_locals(program);
+
+    let minified = Program::new(program.0.iter()
+        .map(|line| minify_line(line, &renames))
+        .collect());
+
+    let compact = Formatter::new(FormatStyle {
+        spacing: Spacing::Compact,
+        keyword_case: KeywordCase::Lower,
+        parens: ParenPolicy::Minimal
+    });
+
+    let lines: Vec<String> = minified.0.iter()
+        .map(|line| compact.format_line(line))
+        .collect();
+
+    let mut source_lines = source.split('\n')
+        .map(|line| line.strip_suffix('\r').unwrap_or(line));
+
+    let sizes = lines.iter()
+        .map(|minified| LineSize {
+            before: source_lines.next().map_or(0, |line| line.chars().count()),
+            after: minified.chars().count()
+        })
+        .collect();
+
+    Minified {
+        program: minified,
+        source: lines.iter().map(|line| format!("{}\n", line)).collect(),
+        sizes,
+        renames: renames.into_values()
+            .map(|(original, ident)| (original, ident.name))
+            .collect()
+    }
+}
+
+// Gives every local a new name, shortest first in order of how often it's used
+fn rename_locals(program: &Program) -> HashMap<String, (String, Ident)>
+{
+    let mut counts: Vec<(String, usize)> = Vec::new();
+
+    for line in &program.0
+    {
+        for statement in &line.0
+        {
+            visit_statement(&statement.node, &mut |ident| {
+                match counts.iter_mut().find(|(counted, _)| *counted == ident.key)
+                {
+                    Some((_, count)) => *count += 1,
+                    None => counts.push((ident.key.clone(), 1))
+                }
+            });
+        }
+    }
+
+    // The sort is stable, so ties keep the order the locals first show up in
+    counts.sort_by(|(_, left), (_, right)| right.cmp(left));
+
+    counts.into_iter()
+        .zip(short_names())
+        .map(|((original, _), name)| (original.clone(), (original, Ident::new(&name))))
+        .collect()
+}
+
+// Every name a local can have, shortest first
+fn short_names() -> impl Iterator<Item = String>
+{
+    let letters = || (b'a'..=b'z').map(char::from);
+
+    (1..).flat_map(move |length| {
+        let mut names = vec![String::new()];
+        for _ in 0..length
+        {
+            names = names.into_iter()
+                .flat_map(|name| letters().map(move |letter| format!("{}{}", name, letter)))
+                .collect();
+        }
+
+        names
+    })
+    .filter(|name| !KEYWORDS.contains(&name.as_str()))
+}
+
+fn visit_statement(statement: &Stat, visit: &mut impl FnMut(&Ident))
+{
+    match statement
+    {
+        Stat::Comment(_) => {},
+        Stat::If(cond, body, else_body) => {
+            visit_expression(cond, visit);
+            for statement in body.iter().chain(else_body.iter().flatten())
+            {
+                visit_statement(statement, visit);
+            }
+        },
+        Stat::Goto(expr) |
+        Stat::Expression(expr) => visit_expression(expr, visit),
+        Stat::Assignment(ident, _, expr) => {
+            visit_value(ident, visit);
+            visit_expression(expr, visit);
+        }
+    }
+}
+
+fn visit_expression(expr: &Expr, visit: &mut impl FnMut(&Ident))
+{
+    match expr
+    {
+        Expr::BinaryOp(_, left, right) => {
+            visit_expression(left, visit);
+            visit_expression(right, visit);
+        },
+        Expr::UnaryOp(_, operand) => visit_expression(operand, visit),
+        Expr::Value(value) => visit_value(value, visit)
+    }
+}
+
+fn visit_value(value: &Value, visit: &mut impl FnMut(&Ident))
+{
+    match value
+    {
+        Value::Group(expr) => visit_expression(expr, visit),
+        Value::LocalVar(ident) => visit(ident),
+        _ => {}
+    }
+}
+
+fn minify_line(line: &Line, renames: &HashMap<String, (String, Ident)>) -> Line
+{
+    Line(minify_body(&line.0, renames))
+}
+
+fn minify_body(body: &[Spanned<Stat>], renames: &HashMap<String, (String, Ident)>) -> Vec<Spanned<Stat>>
+{
+    body.iter()
+        .filter_map(|statement| minify_statement(statement, renames))
+        .collect()
+}
+
+// Returns None for statements that can be dropped
+fn minify_statement(statement: &Spanned<Stat>, renames: &HashMap<String, (String, Ident)>) -> Option<Spanned<Stat>>
+{
+    let minified = match &statement.node
+    {
+        Stat::Comment(_) => return None,
+        Stat::If(cond, body, else_body) => {
+            let else_body = else_body.as_ref()
+                .map(|else_body| minify_body(else_body, renames))
+                .filter(|else_body| !else_body.is_empty());
+
+            Stat::If(Box::new(minify_expression(cond, renames)), minify_body(body, renames), else_body)
+        },
+        Stat::Goto(expr) => Stat::Goto(Box::new(minify_expression(expr, renames))),
+        Stat::Assignment(ident, op, expr) => Stat::Assignment(rename_value(ident, renames), *op, Box::new(minify_expression(expr, renames))),
+        Stat::Expression(expr) => Stat::Expression(Box::new(minify_expression(expr, renames)))
+    };
+
+    Some(Spanned::new(minified, statement.span))
+}
+
+fn minify_expression(expr: &Spanned<Expr>, renames: &HashMap<String, (String, Ident)>) -> Spanned<Expr>
+{
+    let minified = match &expr.node
+    {
+        Expr::BinaryOp(op, left, right) => Expr::BinaryOp(*op, Box::new(minify_expression(left, renames)), Box::new(minify_expression(right, renames))),
+        Expr::UnaryOp(op, operand) => Expr::UnaryOp(*op, Box::new(minify_expression(operand, renames))),
+        Expr::Value(value) => {
+            let value = match value
+            {
+                Value::Group(inner) => Value::Group(Box::new(minify_expression(inner, renames))),
+                value => rename_value(value, renames)
+            };
+
+            return Spanned::new(Expr::Value(value), expr.span);
+        }
+    };
+
+    fold(Spanned::new(minified, expr.span))
+}
+
+fn rename_value(value: &Value, renames: &HashMap<String, (String, Ident)>) -> Value
+{
+    match value
+    {
+        Value::LocalVar(ident) => match renames.get(&ident.key)
+        {
+            Some((_, renamed)) => Value::LocalVar(renamed.clone()),
+            None => value.clone()
+        },
+        value => value.clone()
+    }
+}
+
+// Replaces an operation on constants with its result, as long as the result is shorter
+fn fold(expr: Spanned<Expr>) -> Spanned<Expr>
+{
+    let folded = match optimizer::fold_constant(&expr)
+    {
+        Some(folded) => folded,
+        None => return expr
+    };
+
+    let formatter = Formatter::new(FormatStyle {
+        spacing: Spacing::Compact,
+        keyword_case: KeywordCase::Lower,
+        parens: ParenPolicy::Minimal
+    });
+
+    if formatter.format_expression(&folded).len() <= formatter.format_expression(&expr).len()
+    {
+        folded
+    }
+    else
+    {
+        expr
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    use crate::chip::Chip;
+    use crate::types::TokenWindow;
+    use crate::tokenizer;
+    use crate::parser;
+
+    fn parse(source: &str) -> Program
+    {
+        let tokens = tokenizer::tokenize(String::from(source)).unwrap();
+        parser::parse_program(&mut TokenWindow::from(tokens)).unwrap()
+    }
+
+    #[test]
+    fn the_most_used_locals_get_the_shortest_names()
+    {
+        let source = "rarely = 1 often = 2 // counts\noften += often * (2 + 3)\n:out = often + rarely goto 1\n";
+        let minified = minify(&parse(source), source);
+
+        assert_eq!(minified.renames.get("often").map(String::as_str), Some("a"));
+        assert_eq!(minified.renames.get("rarely").map(String::as_str), Some("b"));
+        assert_eq!(minified.source, "b=1 a=2\na+=a*5\n:out=a+b goto 1\n");
+
+        let mut original = Chip::new("original", parse(source));
+        let mut shrunk = Chip::new("minified", minified.program);
+        original.run_ticks(6).unwrap();
+        shrunk.run_ticks(6).unwrap();
+
+        assert_eq!(original.env.global_context(), shrunk.env.global_context());
+    }
+
+    #[test]
+    fn sizes_before_are_measured_from_the_source()
+    {
+        let source = "counter   =   1   // start\r\nCOUNTER++\n";
+        let minified = minify(&parse(source), source);
+
+        assert_eq!(minified.source, "a=1\na++\n");
+        assert_eq!(minified.sizes, [LineSize { before: 26, after: 3 }, LineSize { before: 9, after: 3 }]);
+    }
+}
@@ -0,0 +1,215 @@
+use std::mem;
+
+use crate::types::ast::value::LiteralValue;
+use crate::types::NetworkError;
+
+use crate::environment::{ContextMap, Variables};
+use crate::chip::Chip;
+use crate::device::Devices;
+
+/// Many chips connected to one data field bus, like the chips on a ship.
+/// Each tick, every chip executes one line in the order they were added.
+/// Data fields written by a chip are visible to the chips after it in the same tick.
+/// The data fields live on the network, so a chip's own environment doesn't hold any while it's part of one.
+#[derive(Debug, Clone, Default)]
+pub struct Network
+{
+    chips: Vec<Chip>,
+    data_fields: Variables,
+
+    /// Simulated hardware on the bus, shared by all the chips
+    pub devices: Devices,
+
+    ticks: u64
+}
+
+impl Network
+{
+    pub fn new() -> Network
+    {
+        Network::default()
+    }
+
+    /// Adds a chip to the end of the execution order and returns its index.
+    /// Any data fields the chip already has are written to the bus.
+    pub fn add_chip(&mut self, mut chip: Chip) -> usize
+    {
+        self.data_fields.append(mem::take(chip.env.global_context_mut()));
+
+        self.chips.push(chip);
+        self.chips.len() - 1
+    }
+
+    pub fn chips(&self) -> &[Chip]
+    {
+        &self.chips
+    }
+
+    pub fn chip(&self, index: usize) -> Option<&Chip>
+    {
+        self.chips.get(index)
+    }
+
+    pub fn chip_mut(&mut self, index: usize) -> Option<&mut Chip>
+    {
+        self.chips.get_mut(index)
+    }
+
+    /// Returns the value of a data field on the bus, like `:door`.
+    /// Fields nobody has written to are 0, the same as in a chip.
+    pub fn get_field(&self, name: &str) -> LiteralValue
+    {
+        self.data_fields.get(name)
+            .cloned()
+            .unwrap_or_else(LiteralValue::get_false)
+    }
+
+    pub fn set_field(&mut self, name: &str, value: LiteralValue)
+    {
+        self.data_fields.set(name, value);
+    }
+
+    pub fn data_fields(&self) -> &Variables
+    {
+        &self.data_fields
+    }
+
+    /// Returns how many ticks the network has executed so far
+    pub fn ticks(&self) -> u64
+    {
+        self.ticks
+    }
+
+    /// Executes one line on every chip, in order.
+    /// Stops at the first chip that errors, leaving the chips after it un-stepped for this tick.
+    pub fn step(&mut self) -> Result<(), NetworkError>
+    {
+        self.ticks += 1;
+
+        let snapshot = self.devices.before_tick(&mut self.data_fields);
+        let result = self.step_chips();
+        self.devices.after_tick(&self.data_fields, snapshot);
+
+        result
+    }
+
+    fn step_chips(&mut self) -> Result<(), NetworkError>
+    {
+        for (index, chip) in self.chips.iter_mut().enumerate()
+        {
+            // The chip works directly on the bus while it runs, then it's handed back
+            mem::swap(&mut self.data_fields, chip.env.global_context_mut());
+            let result = chip.step();
+            mem::swap(&mut self.data_fields, chip.env.global_context_mut());
+
+            if let Err(error) = result
+            {
+                return Err(NetworkError {
+                    chip: index,
+                    name: chip.env.name.clone(),
+                    error
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Executes `count` ticks, stopping at the first error
+    pub fn run_ticks(&mut self, count: u64) -> Result<(), NetworkError>
+    {
+        for _ in 0..count
+        {
+            self.step()?;
+        }
+
+        Ok(())
+    }
+}
+
+// Locals don't exist outside a chip, so everything is treated as a data field on the bus
+impl ContextMap for Network
+{
+    fn get_val(&self, ident: &str) -> LiteralValue
+    {
+        self.get_field(ident)
+    }
+
+    fn set_val(&mut self, ident: String, value: LiteralValue)
+    {
+        self.set_field(&ident, value);
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    use crate::device::Lamp;
+
+    fn chip(name: &str, source: &str) -> Chip
+    {
+        Chip::from_source(name, String::from(source)).unwrap()
+    }
+
+    #[test]
+    fn later_chips_see_fields_written_in_the_same_tick()
+    {
+        let mut network = Network::new();
+        network.add_chip(chip("reader", "a = :x goto 1\n"));
+        network.add_chip(chip("writer", ":x += 1 goto 1\n"));
+        network.add_chip(chip("copier", ":y = :X goto 1\n"));
+
+        network.run_ticks(2).unwrap();
+
+        assert_eq!(network.get_field(":y"), LiteralValue::from(2));
+        assert_eq!(network.chip(0).unwrap().env.get_val("a"), LiteralValue::from(1));
+        assert_eq!(network.ticks(), 2);
+    }
+
+    #[test]
+    fn chips_fields_move_onto_the_bus()
+    {
+        let mut first = chip("first", "goto 1\n");
+        first.env.set_val(String::from(":Door"), LiteralValue::from(1));
+
+        let mut network = Network::new();
+        network.add_chip(first);
+
+        assert_eq!(network.get_field(":door"), LiteralValue::from(1));
+        assert_eq!(network.data_fields().name(":door"), Some(":Door"));
+        assert!(network.chip(0).unwrap().env.global_context().is_empty());
+    }
+
+    #[test]
+    fn errors_name_the_chip_and_stop_the_tick()
+    {
+        let mut network = Network::new();
+        network.add_chip(chip("fine", ":a += 1 goto 1\n"));
+        network.add_chip(chip("broken", "b = -\"x\" goto 1\n"));
+        network.add_chip(chip("skipped", ":c += 1 goto 1\n"));
+
+        let error = network.step().unwrap_err();
+
+        assert_eq!(error.chip, 1);
+        assert_eq!(error.name, "broken");
+        assert_eq!(network.get_field(":a"), LiteralValue::from(1));
+        assert_eq!(network.get_field(":c"), LiteralValue::from(0));
+    }
+
+    #[test]
+    fn devices_see_the_bus_after_every_chip_ran()
+    {
+        let mut network = Network::new();
+        let lamp = network.devices.register(Lamp::new(":Lamp"));
+        network.add_chip(chip("on", ":lamp = 1 goto 1\n"));
+        network.add_chip(chip("off", ":lamp = 0\ngoto 1\n"));
+
+        network.step().unwrap();
+        assert!(!network.devices.get::<Lamp>(lamp).unwrap().is_on());
+
+        network.step().unwrap();
+        assert!(network.devices.get::<Lamp>(lamp).unwrap().is_on());
+    }
+}
@@ -0,0 +1,335 @@
+use crate::types::ast::{
+    statement::Statement as Stat,
+    expression::Expression as Expr,
+    operators::Operator as Op,
+    value::{Value, LiteralValue},
+    line::Line,
+    program::Program
+};
+
+use crate::types::{Span, Spanned};
+
+use crate::interpreter;
+use crate::formatter::{Formatter, FormatStyle, Spacing, KeywordCase, ParenPolicy};
+
+/// Simplifies a program without changing what it does.
+///
+/// Constant expressions are folded with the same operators the interpreter uses, ifs with constant conditions
+/// are replaced by the branch they'd take, statements after a goto are dropped and identities like `x + 0` are
+/// simplified where the types allow it. Every line stays on the same line number, but runtime errors may be
+/// reported against a different statement of the line.
+pub fn optimize(program: &Program) -> Program
+{
+    Program::new(program.0.iter().map(optimize_line).collect())
+}
+
+/// Prints an optimized program without any spaces or parentheses it doesn't need.
+/// Folded constants can be longer than the expressions they replace, so the spaced standard format
+/// could push lines that fit on a chip past its limit.
+pub fn format_optimized(program: &Program) -> String
+{
+    let formatter = Formatter::new(FormatStyle {
+        spacing: Spacing::Compact,
+        keyword_case: KeywordCase::Lower,
+        parens: ParenPolicy::Minimal
+    });
+
+    formatter.format_program(program)
+}
+
+pub fn optimize_line(line: &Line) -> Line
+{
+    Line(optimize_body(&line.0))
+}
+
+/// Optimizes a statement, which can turn it into any number of statements when it's an if.
+/// The statements of a pruned if keep their own spans.
+pub fn optimize_statement(statement: &Spanned<Stat>) -> Vec<Spanned<Stat>>
+{
+    let optimized = match &statement.node
+    {
+        Stat::Comment(_) => statement.node.clone(),
+        Stat::If(cond, body, else_body) => {
+            let cond = optimize_expression(cond);
+            let body = optimize_body(body);
+            let else_body = else_body.as_ref().map(|else_body| optimize_body(else_body));
+
+            match constant_value(&cond)
+            {
+                Some(value) if value == LiteralValue::get_false() => return else_body.unwrap_or_default(),
+                Some(_) => return body,
+                None => Stat::If(Box::new(cond), body, else_body)
+            }
+        },
+        Stat::Goto(expr) => Stat::Goto(Box::new(optimize_expression(expr))),
+        Stat::Assignment(ident, op, expr) => Stat::Assignment(ident.clone(), *op, Box::new(optimize_expression(expr))),
+        Stat::Expression(expr) => Stat::Expression(Box::new(optimize_expression(expr)))
+    };
+
+    vec![Spanned::new(optimized, statement.span)]
+}
+
+// A goto jumps out of the if and the line, so anything after it is dropped
+fn optimize_body(body: &[Spanned<Stat>]) -> Vec<Spanned<Stat>>
+{
+    let mut statements = Vec::new();
+
+    for statement in body
+    {
+        statements.extend(optimize_statement(statement));
+
+        if let Some(Spanned { node: Stat::Goto(_), .. }) = statements.last()
+        {
+            break;
+        }
+    }
+
+    statements
+}
+
+pub fn optimize_expression(expr: &Spanned<Expr>) -> Spanned<Expr>
+{
+    let optimized = match &expr.node
+    {
+        Expr::BinaryOp(op, left, right) => Expr::BinaryOp(*op, Box::new(optimize_expression(left)), Box::new(optimize_expression(right))),
+        Expr::UnaryOp(op, operand) => Expr::UnaryOp(*op, Box::new(optimize_expression(operand))),
+        Expr::Value(Value::Group(inner)) => {
+            // Parentheses around a single value don't do anything
+            let inner = optimize_expression(inner);
+            return match inner.node
+            {
+                Expr::Value(_) => inner,
+                _ => Spanned::new(Expr::Value(Value::Group(Box::new(inner))), expr.span)
+            };
+        },
+        Expr::Value(_) => return expr.clone()
+    };
+
+    let optimized = Spanned::new(optimized, expr.span);
+
+    match fold_constant(&optimized)
+    {
+        Some(folded) => folded,
+        None => simplify(optimized)
+    }
+}
+
+/// Evaluates an operation whose operands are all constants, returning an expression for the result.
+/// Operations that would fail are left alone, so the error still happens when the program runs.
+pub fn fold_constant(expr: &Spanned<Expr>) -> Option<Spanned<Expr>>
+{
+    let value = match &expr.node
+    {
+        Expr::BinaryOp(op, left, right) => {
+            interpreter::apply_binary_op(*op, constant_value(left)?, constant_value(right)?).ok()?
+        },
+        // Increments and decrements need a variable, so they're never constant
+        Expr::UnaryOp(Op::PreInc | Op::PostInc | Op::PreDec | Op::PostDec, _) => return None,
+        Expr::UnaryOp(op, operand) => interpreter::apply_unary_op(*op, constant_value(operand)?).ok()?,
+        Expr::Value(_) => return None
+    };
+
+    literal_expression(value, expr.span)
+}
+
+/// The value of an expression that's just a constant, looking through any parentheses
+pub fn constant_value(expr: &Expr) -> Option<LiteralValue>
+{
+    match expr
+    {
+        Expr::Value(Value::Group(expr)) => constant_value(expr),
+        Expr::Value(Value::NumberVal(num)) => Some(LiteralValue::NumberVal(*num)),
+        Expr::Value(Value::StringVal(string)) => Some(LiteralValue::StringVal(string.clone())),
+        Expr::UnaryOp(Op::Negate, operand) => match constant_value(operand)?
+        {
+            num @ LiteralValue::NumberVal(_) => interpreter::apply_unary_op(Op::Negate, num).ok(),
+            _ => None
+        },
+        _ => None
+    }
+}
+
+// Writes a value as an expression that evaluates back to it
+fn literal_expression(value: LiteralValue, span: Span) -> Option<Spanned<Expr>>
+{
+    let expr = match value
+    {
+        LiteralValue::StringVal(string) => Expr::Value(Value::StringVal(string)),
+        LiteralValue::NumberVal(num) if num.to_string().starts_with('-') => {
+            // Source can't have negative numbers, only negated ones, so the number has to negate back exactly
+            let positive = interpreter::apply_unary_op(Op::Negate, LiteralValue::NumberVal(num)).ok()?;
+            let negated = interpreter::apply_unary_op(Op::Negate, positive.clone()).ok()?;
+
+            match positive
+            {
+                LiteralValue::NumberVal(positive) if negated == LiteralValue::NumberVal(num) => {
+                    let positive = Spanned::new(Expr::Value(Value::NumberVal(positive)), span);
+                    Expr::UnaryOp(Op::Negate, Box::new(positive))
+                },
+                _ => return None
+            }
+        },
+        LiteralValue::NumberVal(num) => Expr::Value(Value::NumberVal(num))
+    };
+
+    Some(Spanned::new(expr, span))
+}
+
+// Removes operations that don't change their operand.
+// Most only hold for numbers, since strings and numbers add differently, and multiplying saturates big numbers.
+fn simplify(expr: Spanned<Expr>) -> Spanned<Expr>
+{
+    let is_constant = |expr: &Expr, expected: i64| constant_value(expr) == Some(LiteralValue::from(expected));
+
+    let span = expr.span;
+    match expr.node
+    {
+        Expr::BinaryOp(Op::Add, left, right) if is_constant(&right, 0) && is_number(&left) => *left,
+        Expr::BinaryOp(Op::Add, left, right) if is_constant(&left, 0) && is_number(&right) => *right,
+        Expr::BinaryOp(Op::Sub, left, right) if is_constant(&right, 0) && is_number(&left) => *left,
+
+        Expr::BinaryOp(Op::And, left, right) if is_constant(&right, 1) && is_boolean(&left) => *left,
+        Expr::BinaryOp(Op::And, left, right) if is_constant(&left, 1) && is_boolean(&right) => *right,
+        Expr::BinaryOp(Op::Or, left, right) if is_constant(&right, 0) && is_boolean(&left) => *left,
+        Expr::BinaryOp(Op::Or, left, right) if is_constant(&left, 0) && is_boolean(&right) => *right,
+
+        Expr::UnaryOp(Op::Not, operand) => match operand.node
+        {
+            Expr::UnaryOp(Op::Not, inner) if is_boolean(&inner) => *inner,
+            _ => Spanned::new(Expr::UnaryOp(Op::Not, operand), span)
+        },
+
+        expr => Spanned::new(expr, span)
+    }
+}
+
+// Whether the expression always evaluates to a number, if it doesn't fail
+fn is_number(expr: &Expr) -> bool
+{
+    match expr
+    {
+        Expr::Value(Value::Group(expr)) => is_number(expr),
+        Expr::Value(Value::NumberVal(_)) => true,
+        Expr::Value(_) => false,
+        // Adding or subtracting a string gives a string
+        Expr::BinaryOp(Op::Add | Op::Sub, left, right) => is_number(left) && is_number(right),
+        Expr::BinaryOp(_, _, _) => true,
+        // Incrementing or decrementing a variable gives whatever type the variable is
+        Expr::UnaryOp(Op::PreInc | Op::PostInc | Op::PreDec | Op::PostDec, _) => false,
+        Expr::UnaryOp(_, _) => true
+    }
+}
+
+// Whether the expression always evaluates to 0 or 1, if it doesn't fail
+fn is_boolean(expr: &Expr) -> bool
+{
+    match expr
+    {
+        Expr::Value(Value::Group(expr)) => is_boolean(expr),
+        Expr::BinaryOp(op, _, _) => matches!(op,
+            Op::Lesser | Op::Greater | Op::LesserEq | Op::GreaterEq |
+            Op::Equal | Op::NotEqual | Op::And | Op::Or),
+        Expr::UnaryOp(Op::Not, _) => true,
+        expr => matches!(constant_value(expr), Some(value) if value == LiteralValue::get_false() || value == LiteralValue::get_true())
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    use crate::types::TokenWindow;
+    use crate::tokenizer;
+    use crate::parser;
+    use crate::formatter;
+    use crate::types::ChipLimits;
+
+    fn parse(source: &str) -> Program
+    {
+        let tokens = tokenizer::tokenize(String::from(source)).unwrap();
+        parser::parse_program(&mut TokenWindow::from(tokens)).unwrap()
+    }
+
+    fn assert_optimizes_to(source: &str, expected: &str)
+    {
+        let optimized = formatter::format_program(&optimize(&parse(source)));
+        assert_eq!(optimized, formatter::format_program(&parse(expected)), "optimizing {:?}", source);
+    }
+
+    #[test]
+    fn constants_are_folded()
+    {
+        assert_optimizes_to("a = 2 * 3 + 1\n", "a = 7\n");
+        assert_optimizes_to("a = \"ab\" + 1\n", "a = \"ab1\"\n");
+        assert_optimizes_to("a = 1 - 3\n", "a = -2\n");
+        assert_optimizes_to("a = (b)\n", "a = b\n");
+    }
+
+    #[test]
+    fn failing_operations_are_left_to_fail()
+    {
+        assert_optimizes_to("a = 1 / 0\n", "a = 1 / 0\n");
+        assert_optimizes_to("a = -\"x\"\n", "a = -\"x\"\n");
+    }
+
+    #[test]
+    fn constant_ifs_become_their_branch()
+    {
+        assert_optimizes_to("if 1 then a = 1 else a = 2 end b = 3\n", "a = 1 b = 3\n");
+        assert_optimizes_to("if 0 then a = 1 else a = 2 end\n", "a = 2\n");
+        assert_optimizes_to("if 0 then a = 1 end\n", "\n");
+        assert_optimizes_to("if b then a = 1 + 1 end\n", "if b then a = 2 end\n");
+    }
+
+    #[test]
+    fn statements_after_a_goto_are_dropped()
+    {
+        assert_optimizes_to("a = 1 goto 1 b = 2\n", "a = 1 goto 1\n");
+        assert_optimizes_to("if b then goto 2 c = 1 end d = 1\n", "if b then goto 2 end d = 1\n");
+    }
+
+    #[test]
+    fn identities_only_go_when_the_types_allow()
+    {
+        assert_optimizes_to("a = (b * 2) + 0\n", "a = (b * 2)\n");
+        assert_optimizes_to("a = b + 0\n", "a = b + 0\n");
+        assert_optimizes_to("a = (b > 1) and 1\n", "a = (b > 1)\n");
+        assert_optimizes_to("a = not not (b == 1)\n", "a = (b == 1)\n");
+        assert_optimizes_to("a = not not b\n", "a = not not b\n");
+    }
+
+    #[test]
+    fn lines_keep_their_numbers()
+    {
+        let optimized = optimize(&parse("if 0 then a = 1 end\nb = 1 + 1\n"));
+
+        assert_eq!(optimized.0.len(), 2);
+        assert!(optimized.0[0].0.is_empty());
+        assert_eq!(optimized.0[1].0[0].span.line, 2);
+    }
+
+    #[test]
+    fn optimized_fixtures_still_fit_on_a_chip()
+    {
+        let fixtures = [
+            ("basicyolol.yolol", include_str!("../basicyolol.yolol")),
+            ("gross_test.yolol", include_str!("../gross_test.yolol")),
+            ("parentest.yolol", include_str!("../parentest.yolol")),
+            ("problem_test.yolol", include_str!("../problem_test.yolol")),
+            ("torturetest.yolol", include_str!("../torturetest.yolol")),
+            ("xor_test.yolol", include_str!("../xor_test.yolol")),
+            ("yololTest.yolol", include_str!("../yololTest.yolol")),
+            ("yovec_test.yolol", include_str!("../yovec_test.yolol"))
+        ];
+
+        for (name, source) in fixtures
+        {
+            let optimized = format_optimized(&optimize(&parse(source)));
+
+            let tokens = tokenizer::tokenize(optimized.clone()).unwrap();
+            let reparsed = parser::parse_program_with_limits(&mut TokenWindow::from(tokens), &ChipLimits::default());
+            assert!(reparsed.is_ok(), "optimized {} doesn't parse: {:?}\n{}", name, reparsed.err(), optimized);
+        }
+    }
+}
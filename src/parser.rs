@@ -1,3 +1,5 @@
+use log::{trace, debug};
+
 use crate::types::Token;
 
 use crate::types::ast::{
@@ -10,80 +12,115 @@ use crate::types::ast::{
 };
 
 use crate::types::ParseErrorKind;
-use crate::types::ExprError;
-use crate::types::StatError;
+use crate::types::ParseError;
 
 use crate::types::SlidingWindow;
-use crate::types::VecWindow;
+use crate::types::TokenWindow;
+use crate::types::Spanned;
+use crate::types::Span;
+use crate::types::ChipLimits;
+
+pub fn parse_program(window: &mut TokenWindow) -> Result<Program, ParseError>
+{
+    parse_program_with_limits(window, &ChipLimits::unlimited())
+}
 
-pub fn parse_program(window: &mut VecWindow<Token>) -> Result<Program, StatError>
+/// Parses a program, erroring if any line is too long or there are too many lines for the chip.
+/// Empty lines past the end of the chip are allowed, since they never do anything.
+pub fn parse_program_with_limits(window: &mut TokenWindow, limits: &ChipLimits) -> Result<Program, ParseError>
 {
     let mut line_vec: Vec<Line> = Vec::new();
-    let mut current_line: Vec<Stat> = Vec::new();
+    let mut current_line: Vec<Spanned<Stat>> = Vec::new();
+
+    // Byte offset of the start of the current line, used to build the span of a whole line
+    let mut line_start = window.current_span().offset;
 
     while window.remaining_length() > 0
     {
         if let Some(Token::Newline) = window.get_value(0)
         {
+            let newline_span = window.current_span();
+            check_line_limits(limits, line_vec.len(), &current_line, line_start, newline_span)?;
+
             window.move_view(1);
+            line_start = newline_span.end();
 
             line_vec.push(Line(current_line.clone()));
-            if cfg!(debug_assertions) { println!("[Parser] Finished line:\n{:?}", current_line) }
+            trace!("Finished line:\n{:?}", current_line);
 
             current_line.clear();
             continue;
         }
 
-        match parse_statement(window)
+        match parse_spanned_statement(window)
         {
             Ok(stat) => {
-                if cfg!(debug_assertions) { println!("[Parser] Parsed statement: {:?}", stat) }
+                trace!("Parsed statement: {:?}", stat);
                 current_line.push(stat);
             },
 
-            error => {
-                if cfg!(debug_assertions) {
-                    println!("[Parser] Erroring out, line so far:\n{:?}", line_vec);
-                    println!("[Parser] Erroring out, window state:\n{:?}", window.get_window(3));
-                }
-                error?;
+            Err(error) => {
+                debug!("Erroring out, line so far:\n{:?}", line_vec);
+                debug!("Erroring out, window state:\n{:?}", window.get_window(3));
+                return Err(error);
             }
         }
     }
 
     if current_line.is_empty() == false
     {
+        check_line_limits(limits, line_vec.len(), &current_line, line_start, window.current_span())?;
         line_vec.push(Line(current_line.clone()));
     }
 
-    Ok(Program(line_vec))
+    Ok(Program::new(line_vec))
+}
+
+// Checks the line that ends at the given span against the chip limits.
+// The end span's column is one past the last character of the line, so it gives us the length in characters.
+fn check_line_limits(limits: &ChipLimits, line_index: usize, line: &[Spanned<Stat>], line_start: usize, end: Span) -> Result<(), ParseError>
+{
+    let line_span = Span::new(end.line, 1, line_start, end.offset.saturating_sub(line_start));
+
+    let length = end.column.saturating_sub(1);
+    if length > limits.max_line_chars
+    {
+        let kind = ParseErrorKind::LineTooLong { length, max: limits.max_line_chars };
+        return Err(ParseError::new(kind).with_span(line_span));
+    }
+
+    if line_index >= limits.max_lines && !line.is_empty()
+    {
+        let kind = ParseErrorKind::TooManyLines { max: limits.max_lines };
+        return Err(ParseError::new(kind).with_span(line_span));
+    }
+
+    Ok(())
 }
 
-pub fn parse_line(window: &mut VecWindow<Token>) -> Result<Line, StatError>
+pub fn parse_line(window: &mut TokenWindow) -> Result<Line, ParseError>
 {
-    let mut stat_vec: Vec<Stat> = Vec::new();
+    let mut stat_vec: Vec<Spanned<Stat>> = Vec::new();
     while window.remaining_length() > 0
     {
         if let Some(Token::Newline) = window.get_value(0)
         {
             window.move_view(1);
-            if cfg!(debug_assertions) { println!("[Parser] Finished line:\n{:?}", stat_vec) }
+            trace!("Finished line:\n{:?}", stat_vec);
             break;
         }
 
-        match parse_statement(window)
+        match parse_spanned_statement(window)
         {
             Ok(stat) => {
-                if cfg!(debug_assertions) { println!("[Parser] Parsed statement: {:?}", stat) }
+                trace!("Parsed statement: {:?}", stat);
                 stat_vec.push(stat);
             }
 
-            error => {
-                if cfg!(debug_assertions) {
-                    println!("[Parser] Erroring out, line so far:\n{:?}", stat_vec);
-                    println!("[Parser] Erroring out, window state:\n{:?}", window.get_window(3));
-                }
-                error?;
+            Err(error) => {
+                debug!("Erroring out, line so far:\n{:?}", stat_vec);
+                debug!("Erroring out, window state:\n{:?}", window.get_window(3));
+                return Err(error);
             }
         }
     }
@@ -91,10 +128,46 @@ pub fn parse_line(window: &mut VecWindow<Token>) -> Result<Line, StatError>
     Ok(Line(stat_vec))
 }
 
-fn parse_statement(window: &mut VecWindow<Token>) -> Result<Stat, StatError>
+// Parses a statement and records the span of tokens it was parsed from.
+// Errors are given the span of the token the parser stopped on, since that's where things went wrong.
+fn parse_spanned_statement(window: &mut TokenWindow) -> Result<Spanned<Stat>, ParseError>
+{
+    let start = window.current_span();
+
+    match parse_statement(window)
+    {
+        Ok(stat) => Ok(Spanned::new(stat, span_from(window, start))),
+        Err(error) => Err(error.with_span(window.current_span()))
+    }
+}
+
+// The span from the start of one token to the end of the last token parsed
+fn span_from(window: &TokenWindow, start: Span) -> Span
+{
+    match window.previous_span()
+    {
+        Some(end) => start.to(end),
+        None => start
+    }
+}
+
+fn binary(op: Op, left: Spanned<Expr>, right: Spanned<Expr>) -> Spanned<Expr>
+{
+    let span = left.span.to(right.span);
+    Spanned::new(Expr::BinaryOp(op, Box::new(left), Box::new(right)), span)
+}
+
+// Builds a prefix operation, where `start` is the span of the operator
+fn prefix(op: Op, start: Span, operand: Spanned<Expr>) -> Spanned<Expr>
+{
+    let span = start.to(operand.span);
+    Spanned::new(Expr::UnaryOp(op, Box::new(operand)), span)
+}
+
+fn parse_statement(window: &mut TokenWindow) -> Result<Stat, ParseError>
 {
     let value_tuple = (window.get_value(0), window.get_value(1), window.get_value(2));
-    if cfg!(debug_assertions) { println!("[Parse Stat] Matching slice: {:?}", value_tuple) }
+    trace!("Matching statement slice: {:?}", value_tuple);
 
     let statement = match value_tuple
     {
@@ -144,6 +217,12 @@ fn parse_statement(window: &mut VecWindow<Token>) -> Result<Stat, StatError>
             Stat::Assignment(value, Op::ModAssign, parse_expression(window)?)
         },
 
+        (Some(ident @ Token::Identifier(_)), Some(Token::Caret), Some(Token::Equal)) => {
+            let value = Value::from(ident.clone());
+            window.move_view(3);
+            Stat::Assignment(value, Op::PowAssign, parse_expression(window)?)
+        },
+
         (Some(ident @ Token::Identifier(_)), Some(Token::Equal), Some(tok)) if *tok != Token::Equal => {
             let value = Value::from(ident.clone());
             window.move_view(2);
@@ -156,11 +235,11 @@ fn parse_statement(window: &mut VecWindow<Token>) -> Result<Stat, StatError>
     Ok(statement)
 }
 
-fn extend_if(window: &mut VecWindow<Token>) -> Result<Stat, StatError>
+fn extend_if(window: &mut TokenWindow) -> Result<Stat, ParseError>
 {
     let condition = parse_expression(window)?;
 
-    if cfg!(debug_assertions) { println!("[Parse If] Condition: {:?}", condition) }
+    trace!("If condition: {:?}", condition);
 
     match window.get_value(0)
     {
@@ -168,13 +247,14 @@ fn extend_if(window: &mut VecWindow<Token>) -> Result<Stat, StatError>
             window.move_view(1);
         },
         
-        tok => return Err(StatError::new(None,
-                        ParseErrorKind::NoExtensionAvailable,
-                        &format!("Can't find 'then' to extend if. Found: {:?}", tok)))
+        tok => return Err(ParseError::new(ParseErrorKind::UnexpectedToken {
+                        found: tok.cloned(),
+                        expected: vec![Token::Then]
+                    }))
     }
 
-    let mut body: Vec<Stat> = Vec::new();
-    let mut else_body: Vec<Stat> = Vec::new();
+    let mut body: Vec<Spanned<Stat>> = Vec::new();
+    let mut else_body: Vec<Spanned<Stat>> = Vec::new();
     let mut parsing_else = false;
     let mut hit_end = false;
 
@@ -186,10 +266,7 @@ fn extend_if(window: &mut VecWindow<Token>) -> Result<Stat, StatError>
             (Some(Token::Else), _) => {
                 if parsing_else
                 {
-                    let error_stat = Stat::If(condition, body, Some(else_body));
-                    return Err(StatError::new(Some(error_stat),
-                                ParseErrorKind::RepeatedElseTokens,
-                                "Found an else token after already finding one for this if!"))
+                    return Err(ParseError::new(ParseErrorKind::RepeatedElseTokens))
                 }
                 window.move_view(1);
                 parsing_else = true;
@@ -201,7 +278,10 @@ fn extend_if(window: &mut VecWindow<Token>) -> Result<Stat, StatError>
                 break
             },
 
-            _ => parse_statement(window)?
+            // An if has to be finished on the line it started on
+            (Some(Token::Newline), _) => break,
+
+            _ => parse_spanned_statement(window)?
         };
 
         if parsing_else
@@ -214,8 +294,24 @@ fn extend_if(window: &mut VecWindow<Token>) -> Result<Stat, StatError>
         }
     }
 
+    if !hit_end
+    {
+        let expected = if parsing_else
+        {
+            vec![Token::End]
+        }
+        else
+        {
+            vec![Token::Else, Token::End]
+        };
+
+        return Err(ParseError::new(ParseErrorKind::UnexpectedToken {
+            found: window.get_value(0).cloned(),
+            expected
+        }));
+    }
 
-    let final_else = if else_body.is_empty() == false
+    let final_else = if !else_body.is_empty()
     {
         Some(else_body)
     }
@@ -224,22 +320,15 @@ fn extend_if(window: &mut VecWindow<Token>) -> Result<Stat, StatError>
         None
     };
 
-    let out_stat = Stat::If(condition, body, final_else);
-
-    if hit_end == false
-    {
-        return Err(StatError::new(Some(out_stat), ParseErrorKind::NoExtensionAvailable, "Didn't hit end while parsing if statement!"));
-    }
-
-    Ok(out_stat)
+    Ok(Stat::If(condition, body, final_else))
 }
 
-fn parse_expression(window: &mut VecWindow<Token>) -> Result<Box<Expr>, ExprError>
+fn parse_expression(window: &mut TokenWindow) -> Result<Box<Spanned<Expr>>, ParseError>
 {
     Ok(Box::new(expr_and(window)?))
 }
 
-fn expr_and(window: &mut VecWindow<Token>) -> Result<Expr, ExprError>
+fn expr_and(window: &mut TokenWindow) -> Result<Spanned<Expr>, ParseError>
 {
     match expr_or(window)
     {
@@ -261,7 +350,7 @@ fn expr_and(window: &mut VecWindow<Token>) -> Result<Expr, ExprError>
     }
 }
 
-fn extend_and(left: Expr, window: &mut VecWindow<Token>) -> Result<Expr, ExprError>
+fn extend_and(left: Spanned<Expr>, window: &mut TokenWindow) -> Result<Spanned<Expr>, ParseError>
 {
     let op = match window.get_value(0)
     {
@@ -277,15 +366,16 @@ fn extend_and(left: Expr, window: &mut VecWindow<Token>) -> Result<Expr, ExprErr
     {
         Ok(right) => {
             // Found a right hand side for our rule, so construct the object
-            let expr = Expr::BinaryOp(op, Box::new(left), Box::new(right));
+            let expr = binary(op, left, right);
             extend_and(expr, window)
         }
 
-        _ => Err(ExprError::new(Some(left), ParseErrorKind::NoExtensionAvailable, "Syntax error in parsing an and!"))
+        // The right hand side failed to parse, so pass its error up
+        error @ Err(_) => error
     }
 }
 
-fn expr_or(window: &mut VecWindow<Token>) -> Result<Expr, ExprError>
+fn expr_or(window: &mut TokenWindow) -> Result<Spanned<Expr>, ParseError>
 {
     match expr_equality(window)
     {
@@ -307,7 +397,7 @@ fn expr_or(window: &mut VecWindow<Token>) -> Result<Expr, ExprError>
     }
 }
 
-fn extend_or(left: Expr, window: &mut VecWindow<Token>) -> Result<Expr, ExprError>
+fn extend_or(left: Spanned<Expr>, window: &mut TokenWindow) -> Result<Spanned<Expr>, ParseError>
 {
     let op = match window.get_value(0)
     {
@@ -323,15 +413,16 @@ fn extend_or(left: Expr, window: &mut VecWindow<Token>) -> Result<Expr, ExprErro
     {
         Ok(right) => {
             // Found a right hand side for our rule, so construct the object
-            let expr = Expr::BinaryOp(op, Box::new(left), Box::new(right));
+            let expr = binary(op, left, right);
             extend_or(expr, window)
         }
 
-        _ => Err(ExprError::new(Some(left), ParseErrorKind::NoExtensionAvailable, "Syntax error in parsing an or!"))
+        // The right hand side failed to parse, so pass its error up
+        error @ Err(_) => error
     }
 }
 
-fn expr_equality(window: &mut VecWindow<Token>) -> Result<Expr, ExprError>
+fn expr_equality(window: &mut TokenWindow) -> Result<Spanned<Expr>, ParseError>
 {
     match expr_order(window)
     {
@@ -355,7 +446,7 @@ fn expr_equality(window: &mut VecWindow<Token>) -> Result<Expr, ExprError>
     }
 }
 
-fn extend_equality(left: Expr, window: &mut VecWindow<Token>) -> Result<Expr, ExprError>
+fn extend_equality(left: Spanned<Expr>, window: &mut TokenWindow) -> Result<Spanned<Expr>, ParseError>
 {
     let op = match (window.get_value(0), window.get_value(1))
     {
@@ -375,15 +466,16 @@ fn extend_equality(left: Expr, window: &mut VecWindow<Token>) -> Result<Expr, Ex
     {
         Ok(right) => {
             // Found a right hand side for our rule, so construct the object
-            let expr = Expr::BinaryOp(op, Box::new(left), Box::new(right));
+            let expr = binary(op, left, right);
             extend_equality(expr, window)
         }
 
-        _ => Err(ExprError::new(Some(left), ParseErrorKind::NoExtensionAvailable, "Syntax error in parsing an equality!"))
+        // The right hand side failed to parse, so pass its error up
+        error @ Err(_) => error
     }
 }
 
-fn expr_order(window: &mut VecWindow<Token>) -> Result<Expr, ExprError>
+fn expr_order(window: &mut TokenWindow) -> Result<Spanned<Expr>, ParseError>
 {
     match expr_additive(window)
     {
@@ -409,7 +501,7 @@ fn expr_order(window: &mut VecWindow<Token>) -> Result<Expr, ExprError>
     }
 }
 
-fn extend_order(left: Expr, window: &mut VecWindow<Token>) -> Result<Expr, ExprError>
+fn extend_order(left: Spanned<Expr>, window: &mut TokenWindow) -> Result<Spanned<Expr>, ParseError>
 {
     let op = match (window.get_value(0), window.get_value(1))
     {
@@ -438,15 +530,16 @@ fn extend_order(left: Expr, window: &mut VecWindow<Token>) -> Result<Expr, ExprE
     {
         Ok(right) => {
             // Found a right hand side for our rule, so construct the object
-            let expr = Expr::BinaryOp(op, Box::new(left), Box::new(right));
+            let expr = binary(op, left, right);
             extend_order(expr, window)
         }
 
-        _ => Err(ExprError::new(Some(left), ParseErrorKind::NoExtensionAvailable, "Syntax error in parsing an order!"))
+        // The right hand side failed to parse, so pass its error up
+        error @ Err(_) => error
     }
 }
 
-fn expr_additive(window: &mut VecWindow<Token>) -> Result<Expr, ExprError>
+fn expr_additive(window: &mut TokenWindow) -> Result<Spanned<Expr>, ParseError>
 {
     match expr_multiply(window)
     {
@@ -469,7 +562,7 @@ fn expr_additive(window: &mut VecWindow<Token>) -> Result<Expr, ExprError>
     }
 }
 
-fn extend_additive(left: Expr, window: &mut VecWindow<Token>) -> Result<Expr, ExprError>
+fn extend_additive(left: Spanned<Expr>, window: &mut TokenWindow) -> Result<Spanned<Expr>, ParseError>
 {
     let op = match window.get_value(0)
     {
@@ -489,15 +582,16 @@ fn extend_additive(left: Expr, window: &mut VecWindow<Token>) -> Result<Expr, Ex
     {
         Ok(right) => {
             // Found a right hand side for our rule, so construct the object
-            let expr = Expr::BinaryOp(op, Box::new(left), Box::new(right));
+            let expr = binary(op, left, right);
             extend_additive(expr, window)
         }
 
-        _ => Err(ExprError::new(Some(left), ParseErrorKind::NoExtensionAvailable, "Syntax error in parsing an additive!"))
+        // The right hand side failed to parse, so pass its error up
+        error @ Err(_) => error
     }
 }
 
-fn expr_multiply(window: &mut VecWindow<Token>) -> Result<Expr, ExprError>
+fn expr_multiply(window: &mut TokenWindow) -> Result<Spanned<Expr>, ParseError>
 {
     match expr_exponent(window)
     {
@@ -521,7 +615,7 @@ fn expr_multiply(window: &mut VecWindow<Token>) -> Result<Expr, ExprError>
     }
 }
 
-fn extend_multiply(left: Expr, window: &mut VecWindow<Token>) -> Result<Expr, ExprError>
+fn extend_multiply(left: Spanned<Expr>, window: &mut TokenWindow) -> Result<Spanned<Expr>, ParseError>
 {
     let op = match window.get_value(0)
     {
@@ -545,16 +639,17 @@ fn extend_multiply(left: Expr, window: &mut VecWindow<Token>) -> Result<Expr, Ex
     {
         Ok(right) => {
             // Found a right hand side for our rule, so construct the object
-            let expr = Expr::BinaryOp(op, Box::new(left), Box::new(right));
+            let expr = binary(op, left, right);
             extend_multiply(expr, window)
         }
 
-        _ => Err(ExprError::new(Some(left), ParseErrorKind::NoExtensionAvailable, "Syntax error in parsing a multiply!"))
+        // The right hand side failed to parse, so pass its error up
+        error @ Err(_) => error
     }
 }
 
 // Doesn't use extension idiom due to being right associative
-fn expr_exponent(window: &mut VecWindow<Token>) -> Result<Expr, ExprError>
+fn expr_exponent(window: &mut TokenWindow) -> Result<Spanned<Expr>, ParseError>
 {
     match expr_postfix(window)
     {
@@ -564,10 +659,9 @@ fn expr_exponent(window: &mut VecWindow<Token>) -> Result<Expr, ExprError>
             {
                 Some(Token::Caret) => {
                     window.move_view(1);
-                    let left = Box::new(expr);
-                    let right = Box::new(expr_exponent(window)?);
+                    let right = expr_exponent(window)?;
 
-                    Ok(Expr::BinaryOp(Op::Pow, left, right))
+                    Ok(binary(Op::Pow, expr, right))
                 },
                 _ => Ok(expr)
             }
@@ -579,7 +673,7 @@ fn expr_exponent(window: &mut VecWindow<Token>) -> Result<Expr, ExprError>
     }
 }
 
-fn expr_postfix(window: &mut VecWindow<Token>) -> Result<Expr, ExprError>
+fn expr_postfix(window: &mut TokenWindow) -> Result<Spanned<Expr>, ParseError>
 {
     match expr_keyword(window)
     {
@@ -593,85 +687,93 @@ fn expr_postfix(window: &mut VecWindow<Token>) -> Result<Expr, ExprError>
     }
 }
 
-fn extend_postfix(expr: Expr, window: &mut VecWindow<Token>) -> Expr
+fn extend_postfix(expr: Spanned<Expr>, window: &mut TokenWindow) -> Spanned<Expr>
 {
     match (window.get_value(0), window.get_value(1))
     {
         (Some(Token::Exclam), Some(Token::Equal)) => expr,
         (Some(Token::Exclam), _) => {
             window.move_view(1);
-            extend_postfix(Expr::UnaryOp(Op::Fact, Box::new(expr)), window)
+            let span = span_from(window, expr.span);
+            extend_postfix(Spanned::new(Expr::UnaryOp(Op::Fact, Box::new(expr)), span), window)
         }
         _ => expr
     }
 }
 
-fn expr_keyword(window: &mut VecWindow<Token>) -> Result<Expr, ExprError>
+fn expr_keyword(window: &mut TokenWindow) -> Result<Spanned<Expr>, ParseError>
 {
     match expr_neg(window)
     {
         // The rule below simply didn't match onto the window, so now it's our turn
-        Err(ExprError { kind: ParseErrorKind::NoParseRuleMatch, .. }) => {
+        Err(ParseError { kind: ParseErrorKind::ExpectedExpression { .. }, .. }) => {
             match window.get_value(0)
             {
                 Some(Token::Abs) => {
+                    let start = window.current_span();
                     window.move_view(1);
-                    let operand = Box::new(expr_keyword(window)?);
+                    let operand = expr_keyword(window)?;
 
-                    Ok(Expr::UnaryOp(Op::Abs, operand))
+                    Ok(prefix(Op::Abs, start, operand))
                 },
                 Some(Token::Sqrt) => {
+                    let start = window.current_span();
                     window.move_view(1);
-                    let operand = Box::new(expr_keyword(window)?);
+                    let operand = expr_keyword(window)?;
 
-                    Ok(Expr::UnaryOp(Op::Sqrt, operand))
+                    Ok(prefix(Op::Sqrt, start, operand))
                 },
                 Some(Token::Sin) => {
+                    let start = window.current_span();
                     window.move_view(1);
-                    let operand = Box::new(expr_keyword(window)?);
+                    let operand = expr_keyword(window)?;
 
-                    Ok(Expr::UnaryOp(Op::Sin, operand))
+                    Ok(prefix(Op::Sin, start, operand))
                 },
                 Some(Token::Cos) => {
+                    let start = window.current_span();
                     window.move_view(1);
-                    let operand = Box::new(expr_keyword(window)?);
+                    let operand = expr_keyword(window)?;
 
-                    Ok(Expr::UnaryOp(Op::Cos, operand))
+                    Ok(prefix(Op::Cos, start, operand))
                 },
                 Some(Token::Tan) => {
+                    let start = window.current_span();
                     window.move_view(1);
-                    let operand = Box::new(expr_keyword(window)?);
+                    let operand = expr_keyword(window)?;
 
-                    Ok(Expr::UnaryOp(Op::Tan, operand))
+                    Ok(prefix(Op::Tan, start, operand))
                 },
                 Some(Token::Arcsin) => {
+                    let start = window.current_span();
                     window.move_view(1);
-                    let operand = Box::new(expr_keyword(window)?);
+                    let operand = expr_keyword(window)?;
 
-                    Ok(Expr::UnaryOp(Op::Arcsin, operand))
+                    Ok(prefix(Op::Arcsin, start, operand))
                 },
                 Some(Token::Arccos) => {
+                    let start = window.current_span();
                     window.move_view(1);
-                    let operand = Box::new(expr_keyword(window)?);
+                    let operand = expr_keyword(window)?;
 
-                    Ok(Expr::UnaryOp(Op::Arccos, operand))
+                    Ok(prefix(Op::Arccos, start, operand))
                 },
                 Some(Token::Arctan) => {
+                    let start = window.current_span();
                     window.move_view(1);
-                    let operand = Box::new(expr_keyword(window)?);
+                    let operand = expr_keyword(window)?;
 
-                    Ok(Expr::UnaryOp(Op::Arctan, operand))
+                    Ok(prefix(Op::Arctan, start, operand))
                 },
                 Some(Token::Not) => {
+                    let start = window.current_span();
                     window.move_view(1);
-                    let operand = Box::new(expr_keyword(window)?);
+                    let operand = expr_keyword(window)?;
 
-                    Ok(Expr::UnaryOp(Op::Not, operand))
+                    Ok(prefix(Op::Not, start, operand))
                 },
 
-                _ => Err(ExprError::new(None,
-                        ParseErrorKind::NoParseRuleMatch,
-                        "In expr_keyword, can't find keyword operator after lower rule failed to match!"))
+                _ => Err(ParseError::new(ParseErrorKind::ExpectedExpression { found: window.get_value(0).cloned() }))
             }
         },
         // The lower rule did match, so just pass back up the expression it created
@@ -685,25 +787,24 @@ fn expr_keyword(window: &mut VecWindow<Token>) -> Result<Expr, ExprError>
     }
 }
 
-fn expr_neg(window: &mut VecWindow<Token>) -> Result<Expr, ExprError>
+fn expr_neg(window: &mut TokenWindow) -> Result<Spanned<Expr>, ParseError>
 {
     match expr_ident(window)
     {
         // The rule below simply didn't match onto the window, so now it's our turn
-        Err(ExprError { kind: ParseErrorKind::NoParseRuleMatch, .. }) => {
+        Err(ParseError { kind: ParseErrorKind::ExpectedExpression { .. }, .. }) => {
             match window.get_value(0)
             {
                 Some(Token::Minus) => {
+                    let start = window.current_span();
                     window.move_view(1);
-                    let operand = Box::new(expr_neg(window)?);
+                    let operand = expr_neg(window)?;
 
-                    Ok(Expr::UnaryOp(Op::Negate, operand))
+                    Ok(prefix(Op::Negate, start, operand))
                 }
 
                 _ => {
-                    Err(ExprError::new(None,
-                        ParseErrorKind::NoParseRuleMatch,
-                        "In expr_neg, can't find minus after lower rule failed to match!"))
+                    Err(ParseError::new(ParseErrorKind::ExpectedExpression { found: window.get_value(0).cloned() }))
                 }
             }
         },
@@ -718,48 +819,51 @@ fn expr_neg(window: &mut VecWindow<Token>) -> Result<Expr, ExprError>
     }
 }
 
-fn expr_ident(window: &mut VecWindow<Token>) -> Result<Expr, ExprError>
+fn expr_ident(window: &mut TokenWindow) -> Result<Spanned<Expr>, ParseError>
 {
+    let start = window.current_span();
     let value_tuple = (window.get_value(0), window.get_value(1), window.get_value(2));
     let expr = match value_tuple
     {
         // Postfix inc/dec operator parsing
         (Some(ident @ Token::Identifier(_)), Some(Token::Plus), Some(Token::Plus)) => {
-            let value = Value::from(ident.clone());
+            let value = Spanned::new(Expr::Value(Value::from(ident.clone())), start);
             window.move_view(3);
             
-            Expr::UnaryOp(Op::PostInc, Box::new(Expr::Value(value)))
+            Expr::UnaryOp(Op::PostInc, Box::new(value))
         },
         (Some(ident @ Token::Identifier(_)), Some(Token::Minus), Some(Token::Minus)) => {
-            let value = Value::from(ident.clone());
+            let value = Spanned::new(Expr::Value(Value::from(ident.clone())), start);
             window.move_view(3);
 
-            Expr::UnaryOp(Op::PostDec, Box::new(Expr::Value(value)))
+            Expr::UnaryOp(Op::PostDec, Box::new(value))
         },
 
         // Prefix inc/dec operator parsing
         (Some(Token::Plus), Some(Token::Plus), Some(ident @ Token::Identifier(_))) => {
-            let value = Value::from(ident.clone());
+            let span = window.get_span(2).unwrap_or(start);
+            let value = Spanned::new(Expr::Value(Value::from(ident.clone())), span);
             window.move_view(3);
 
-            Expr::UnaryOp(Op::PreInc, Box::new(Expr::Value(value)))
+            Expr::UnaryOp(Op::PreInc, Box::new(value))
         },
         (Some(Token::Minus), Some(Token::Minus), Some(ident @ Token::Identifier(_))) => {
-            let value = Value::from(ident.clone());
+            let span = window.get_span(2).unwrap_or(start);
+            let value = Spanned::new(Expr::Value(Value::from(ident.clone())), span);
             window.move_view(3);
 
-            Expr::UnaryOp(Op::PreDec, Box::new(Expr::Value(value)))
+            Expr::UnaryOp(Op::PreDec, Box::new(value))
         },
 
         // Parses into any value, which is then wrapped into an expression
         _ => Expr::Value(parse_value(window)?)
     };
 
-    Ok(expr)
+    Ok(Spanned::new(expr, span_from(window, start)))
 }
 
 
-fn parse_value(window: &mut VecWindow<Token>) -> Result<Value, ExprError>
+fn parse_value(window: &mut TokenWindow) -> Result<Value, ParseError>
 {
     match window.get_value(0)
     {
@@ -783,12 +887,113 @@ fn parse_value(window: &mut VecWindow<Token>) -> Result<Value, ExprError>
                     Ok(Value::Group(output))
                 },
 
-                _ => Err(ExprError::new(Some(*output), ParseErrorKind::UnbalancedParenthesis, "Saw LParen, parsed expr, found no RParen!"))
+                tok => Err(ParseError::new(ParseErrorKind::UnexpectedToken {
+                    found: tok.cloned(),
+                    expected: vec![Token::RParen]
+                }))
             }
         },
 
-        _ => Err(ExprError::new(None, ParseErrorKind::NoParseRuleMatch, "No match while parsing value!"))
+        Some(Token::RParen) => Err(ParseError::new(ParseErrorKind::UnbalancedParenthesis)),
+
+        tok => Err(ParseError::new(ParseErrorKind::ExpectedExpression { found: tok.cloned() }))
     }
 }
 
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    use crate::tokenizer;
+    use crate::types::ChipKind;
+
+    fn window(source: &str) -> TokenWindow
+    {
+        TokenWindow::from(tokenizer::tokenize(String::from(source)).unwrap())
+    }
+
+    fn parse_with_limits(source: &str, limits: ChipLimits) -> Result<Program, ParseError>
+    {
+        parse_program_with_limits(&mut window(source), &limits)
+    }
+
+    #[test]
+    fn statements_and_expressions_span_their_source()
+    {
+        let program = parse_program(&mut window("a = 1\nif b then c = 2 * (d + 3) end\n")).unwrap();
+
+        let stat = &program.0[1].0[0];
+        assert_eq!(stat.span, Span::new(2, 1, 6, 29));
+
+        match &stat.node
+        {
+            Stat::If(_, body, None) => {
+                assert_eq!(body[0].span, Span::new(2, 11, 16, 15));
+
+                match &body[0].node
+                {
+                    Stat::Assignment(_, _, expr) => assert_eq!(expr.span, Span::new(2, 15, 20, 11)),
+                    other => panic!("Expected an assignment, found {:?}", other)
+                }
+            },
+            other => panic!("Expected an if, found {:?}", other)
+        }
+    }
+
+    #[test]
+    fn parse_errors_point_at_their_line()
+    {
+        let error = parse_program(&mut window("a = 1\nb = (2\n")).unwrap_err();
+        assert_eq!(error.span.map(|span| span.line), Some(2));
+    }
+
+    #[test]
+    fn long_lines_are_errors_with_their_span()
+    {
+        let error = parse_with_limits("a = 1\nabc = 12345\n", ChipLimits::new(20, 10)).unwrap_err();
+
+        assert_eq!(error.kind, ParseErrorKind::LineTooLong { length: 11, max: 10 });
+        assert_eq!(error.span, Some(Span::new(2, 1, 6, 11)));
+    }
+
+    #[test]
+    fn lines_at_the_limit_are_fine()
+    {
+        assert!(parse_with_limits("abc = 1234", ChipLimits::new(20, 10)).is_ok());
+    }
+
+    #[test]
+    fn too_many_lines_are_errors()
+    {
+        let source = "a = 1\n".repeat(13);
+        let error = parse_with_limits(&source, ChipKind::Basic.into()).unwrap_err();
+
+        assert_eq!(error.kind, ParseErrorKind::TooManyLines { max: 12 });
+        assert_eq!(error.span.map(|span| span.line), Some(13));
 
+        assert!(parse_with_limits(&source, ChipKind::Advanced.into()).is_ok());
+    }
+
+    #[test]
+    fn empty_lines_past_the_end_are_allowed()
+    {
+        let source = format!("{}\n\n\n", "a = 1\n".repeat(12));
+        assert!(parse_with_limits(&source, ChipKind::Basic.into()).is_ok());
+    }
+
+    #[test]
+    fn pow_assign_parses()
+    {
+        let program = parse_program(&mut window("a ^= 2\n")).unwrap();
+
+        match &program.0[0].0[0].node
+        {
+            Stat::Assignment(Value::LocalVar(name), Op::PowAssign, expr) => {
+                assert_eq!(name.name, "a");
+                assert_eq!(expr.to_string(), "2");
+            },
+            other => panic!("Expected a ^= assignment, found {:?}", other)
+        }
+    }
+}
@@ -0,0 +1,158 @@
+use std::collections::BTreeMap;
+
+use crate::types::ast::value::LiteralValue;
+use crate::types::{YoloxideError, TokenWindow};
+
+use crate::environment::Environment;
+use crate::trace::VarWrite;
+use crate::tokenizer;
+use crate::parser;
+use crate::interpreter;
+
+/// What evaluating a line did
+#[derive(Debug, Clone)]
+pub struct ReplOutput
+{
+    /// Every variable that ended up with a different value, in name order
+    pub changes: Vec<VarWrite>,
+    /// The error that stopped the line, if one did. Changes made before it still stick.
+    pub error: Option<YoloxideError>
+}
+
+/// Evaluates lines one at a time against an environment that's kept between them
+#[derive(Debug, Clone)]
+pub struct Repl
+{
+    pub env: Environment
+}
+
+impl Repl
+{
+    pub fn new(name: &str) -> Repl
+    {
+        Repl {
+            env: Environment::new(name)
+        }
+    }
+
+    /// Throws away every variable, starting again with a fresh environment
+    pub fn reset(&mut self)
+    {
+        self.env = Environment::new(&self.env.name);
+    }
+
+    /// Every local variable and data field, in name order
+    pub fn variables(&self) -> BTreeMap<String, LiteralValue>
+    {
+        self.env.local_context()
+            .chain(self.env.global_context().iter())
+            .map(|(name, value)| (String::from(name), value.clone()))
+            .collect()
+    }
+
+    /// Runs a line of yolol. Like on a chip, a goto or an error skips the rest of the line,
+    /// but the environment's next line is left alone.
+    pub fn eval(&mut self, line: &str) -> ReplOutput
+    {
+        let before = self.variables();
+        let error = self.eval_statements(line).err();
+
+        let changes = self.variables().into_iter()
+            .filter_map(|(name, new)| {
+                let old = before.get(&name).cloned().unwrap_or_else(LiteralValue::get_false);
+                if old == new
+                {
+                    return None;
+                }
+
+                Some(VarWrite { name, old, new })
+            })
+            .collect();
+
+        ReplOutput {
+            changes,
+            error
+        }
+    }
+
+    fn eval_statements(&mut self, line: &str) -> Result<(), YoloxideError>
+    {
+        let tokens = tokenizer::tokenize(String::from(line))?;
+        let line = parser::parse_line(&mut TokenWindow::from(tokens))?;
+
+        let line_number = self.env.next_line;
+        let mut result = Ok(());
+
+        for (index, statement) in line.0.iter().enumerate()
+        {
+            match interpreter::evaluate_line_statement(&mut self.env, line_number, index, statement)
+            {
+                Ok(true) => {},
+                Ok(false) => break,
+                Err(error) => {
+                    result = Err(error.into());
+                    break;
+                }
+            }
+        }
+
+        // A goto would have moved it
+        self.env.next_line = line_number;
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    fn changes(output: &ReplOutput) -> Vec<String>
+    {
+        output.changes.iter()
+            .map(|change| format!("{}: {} -> {}", change.name, change.old, change.new))
+            .collect()
+    }
+
+    #[test]
+    fn eval_reports_the_variables_it_changed()
+    {
+        let mut repl = Repl::new("test");
+        repl.eval("a = 1 :Door = 2");
+
+        let output = repl.eval("a = 1 B = a + 1 :door = 3");
+
+        assert!(output.error.is_none());
+        assert_eq!(changes(&output), vec![":Door: 2 -> 3", "B: 0 -> 2"]);
+    }
+
+    #[test]
+    fn gotos_and_errors_skip_the_rest_of_the_line_but_dont_move_it()
+    {
+        let mut repl = Repl::new("test");
+
+        let output = repl.eval("a = 1 goto 5 b = 2");
+        assert_eq!(changes(&output), vec!["a: 0 -> 1"]);
+        assert_eq!(repl.env.next_line, 1);
+
+        let output = repl.eval("c = 1 d = a / 0 e = 1");
+        assert!(output.error.is_some());
+        assert_eq!(changes(&output), vec!["c: 0 -> 1"]);
+        assert_eq!(repl.env.next_line, 1);
+    }
+
+    #[test]
+    fn environments_survive_json()
+    {
+        let mut repl = Repl::new("test");
+        repl.eval("Abc = \"x\" :door = 1");
+
+        let json = serde_json::to_string(&repl.env).unwrap();
+        let mut loaded = Repl::new("other");
+        loaded.env = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(loaded.env.name, "test");
+        assert_eq!(loaded.variables(), repl.variables());
+        assert_eq!(changes(&loaded.eval("abc = 2")), vec!["Abc: \"x\" -> 2"]);
+    }
+}
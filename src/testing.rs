@@ -0,0 +1,312 @@
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::collections::BTreeMap;
+
+use serde::{Serialize, Deserialize};
+
+use crate::types::ast::value::LiteralValue;
+use crate::types::{ChipKind, EvaluationError};
+
+use crate::environment::{ContextMap, ErrorMode};
+use crate::timeline::{Stimulus, Timeline, json_value};
+use crate::chip::Chip;
+
+/// A yolol unit test: a script, the values it starts with and the values it should end up with.
+///
+/// Specs are written as json, with paths relative to the spec file:
+/// ```text
+/// {
+///     "script": "xor_test.yolol",
+///     "ticks": 8,
+///     "initial": { "a": 5 },
+///     "expect": { "c": 0 },
+///     "expect_ticks": [ { "tick": 0, "values": { "a": 5, "b": 3 } } ]
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TestSpec
+{
+    /// Defaults to the name of the spec file
+    #[serde(default)]
+    pub name: String,
+    pub script: String,
+    pub ticks: u64,
+
+    #[serde(default)]
+    pub chip: ChipKind,
+    /// Defaults to aborting the line like in game, so a runtime error doesn't stop the test
+    #[serde(default = "default_error_mode")]
+    pub error_mode: ErrorMode,
+
+    /// Values set before the first tick, for locals and data fields
+    #[serde(default, with = "json_value::map")]
+    pub initial: BTreeMap<String, LiteralValue>,
+    /// Data fields to set before specific ticks
+    #[serde(default)]
+    pub timeline: Vec<Stimulus>,
+
+    /// Values expected after the last tick
+    #[serde(default, with = "json_value::map")]
+    pub expect: BTreeMap<String, LiteralValue>,
+    /// Values expected right after specific ticks
+    #[serde(default)]
+    pub expect_ticks: Vec<TickExpectation>,
+    /// How many runtime errors the script should hit, if it matters
+    #[serde(default)]
+    pub runtime_errors: Option<usize>
+}
+
+/// Values expected right after the given tick, which counts from 0 like in a timeline
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TickExpectation
+{
+    pub tick: u64,
+    #[serde(with = "json_value::map")]
+    pub values: BTreeMap<String, LiteralValue>
+}
+
+fn default_error_mode() -> ErrorMode
+{
+    ErrorMode::AbortLine
+}
+
+/// Why a test didn't pass
+#[derive(Debug, Clone, PartialEq)]
+pub enum TestFailure
+{
+    /// The script couldn't be read, tokenized or parsed
+    Script(String),
+    /// A line errored in a way the error mode doesn't recover from
+    Evaluation { tick: u64, error: EvaluationError },
+    /// A value didn't match. Without a tick, it's one of the final values.
+    Mismatch { tick: Option<u64>, name: String, expected: LiteralValue, actual: LiteralValue },
+    RuntimeErrors { expected: usize, actual: usize }
+}
+
+impl fmt::Display for TestFailure
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+    {
+        match self
+        {
+            TestFailure::Script(error) => write!(f, "{}", error),
+            TestFailure::Evaluation { tick, error } => write!(f, "tick {}: {}", tick, error),
+            TestFailure::Mismatch { tick: Some(tick), name, expected, actual } => {
+                write!(f, "tick {}: {}: expected {}, found {}", tick, name, expected, actual)
+            },
+            TestFailure::Mismatch { tick: None, name, expected, actual } => {
+                write!(f, "{}: expected {}, found {}", name, expected, actual)
+            },
+            TestFailure::RuntimeErrors { expected, actual } => {
+                write!(f, "expected {} runtime errors, found {}", expected, actual)
+            }
+        }
+    }
+}
+
+/// The outcome of running a test spec
+#[derive(Debug, Clone, PartialEq)]
+pub struct TestReport
+{
+    pub name: String,
+    pub failures: Vec<TestFailure>
+}
+
+impl TestReport
+{
+    pub fn passed(&self) -> bool
+    {
+        self.failures.is_empty()
+    }
+}
+
+impl fmt::Display for TestReport
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+    {
+        if self.passed()
+        {
+            return write!(f, "PASS {}", self.name);
+        }
+
+        write!(f, "FAIL {}", self.name)?;
+        for failure in &self.failures
+        {
+            write!(f, "\n    {}", failure)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl TestSpec
+{
+    pub fn from_json(input: &str) -> Result<TestSpec, serde_json::Error>
+    {
+        serde_json::from_str(input)
+    }
+
+    /// Runs the test, reading the script relative to `base_dir`
+    pub fn run(&self, base_dir: &Path) -> TestReport
+    {
+        let path = base_dir.join(&self.script);
+
+        match fs::read_to_string(&path)
+        {
+            Ok(source) => self.run_source(source),
+            Err(error) => TestReport {
+                name: self.name.clone(),
+                failures: vec![TestFailure::Script(format!("{}: {}", path.display(), error))]
+            }
+        }
+    }
+
+    /// Runs the test on the given source instead of reading the script
+    pub fn run_source(&self, source: String) -> TestReport
+    {
+        TestReport {
+            name: self.name.clone(),
+            failures: self.failures(source)
+        }
+    }
+
+    fn failures(&self, source: String) -> Vec<TestFailure>
+    {
+        let mut chip = match Chip::from_source_with_limits(&self.name, source, self.chip.into())
+        {
+            Ok(chip) => chip,
+            Err(error) => return vec![TestFailure::Script(format!("{}:{}", self.script, error))]
+        };
+
+        chip.env.error_mode = self.error_mode;
+        for (name, value) in &self.initial
+        {
+            chip.env.set_val(name.clone(), value.clone());
+        }
+
+        let timeline = Timeline {
+            stimuli: self.timeline.clone(),
+            record: Vec::new()
+        };
+
+        let mut failures = Vec::new();
+        for tick in 0..self.ticks
+        {
+            timeline.apply(tick, &mut chip.env);
+
+            if let Err(error) = chip.step()
+            {
+                failures.push(TestFailure::Evaluation { tick, error });
+                return failures;
+            }
+
+            for expectation in self.expect_ticks.iter().filter(|expectation| expectation.tick == tick)
+            {
+                check_values(&chip.env, Some(tick), &expectation.values, &mut failures);
+            }
+        }
+
+        check_values(&chip.env, None, &self.expect, &mut failures);
+
+        if let Some(expected) = self.runtime_errors
+        {
+            let actual = chip.env.runtime_errors.len();
+            if actual != expected
+            {
+                failures.push(TestFailure::RuntimeErrors { expected, actual });
+            }
+        }
+
+        failures
+    }
+}
+
+fn check_values<C: ContextMap>(context: &C, tick: Option<u64>, expected: &BTreeMap<String, LiteralValue>, failures: &mut Vec<TestFailure>)
+{
+    for (name, expected) in expected
+    {
+        let actual = context.get_val(name);
+
+        // Values of different types never compare equal, so "1" and 1 are still a mismatch
+        if actual != *expected
+        {
+            failures.push(TestFailure::Mismatch {
+                tick,
+                name: name.clone(),
+                expected: expected.clone(),
+                actual
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    // The specs for the fixtures live next to them in the root of the repo
+    fn repo_dir() -> &'static Path
+    {
+        Path::new(env!("CARGO_MANIFEST_DIR"))
+    }
+
+    #[test]
+    fn every_spec_in_the_repo_passes()
+    {
+        let mut specs = 0;
+        for entry in fs::read_dir(repo_dir()).unwrap()
+        {
+            let path = entry.unwrap().path();
+            let file_name = path.file_name().unwrap().to_string_lossy().into_owned();
+            if !file_name.ends_with(".test.json")
+            {
+                continue;
+            }
+
+            let mut spec = TestSpec::from_json(&fs::read_to_string(&path).unwrap()).unwrap();
+            spec.name = file_name;
+
+            let report = spec.run(repo_dir());
+            assert!(report.passed(), "{}", report);
+
+            specs += 1;
+        }
+
+        assert!(specs > 0, "didn't find any specs");
+    }
+
+    #[test]
+    fn failing_specs_report_every_difference()
+    {
+        let spec = TestSpec::from_json(r#"{
+            "name": "wrong",
+            "script": "xor_test.yolol",
+            "ticks": 8,
+            "initial": { "a": 5 },
+            "expect": { "a": 6, "C": 1 },
+            "expect_ticks": [ { "tick": 0, "values": { "A": 4 } } ],
+            "runtime_errors": 3
+        }"#).unwrap();
+
+        let report = spec.run(repo_dir());
+
+        assert!(!report.passed());
+        assert_eq!(report.to_string(), "\
+FAIL wrong
+    tick 0: A: expected 4, found 5
+    C: expected 1, found 0
+    a: expected 6, found 0.039
+    expected 3 runtime errors, found 0");
+    }
+
+    #[test]
+    fn missing_scripts_fail_the_test()
+    {
+        let spec = TestSpec::from_json(r#"{ "name": "missing", "script": "missing.yolol", "ticks": 1 }"#).unwrap();
+        let report = spec.run(repo_dir());
+
+        assert!(matches!(report.failures.as_slice(), [TestFailure::Script(_)]));
+    }
+}
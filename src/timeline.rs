@@ -0,0 +1,420 @@
+use std::fmt;
+use std::collections::BTreeMap;
+
+use serde::{Serialize, Deserialize};
+
+use yolol_number::YololNumber;
+
+use crate::types::ast::value::LiteralValue;
+use crate::types::{EvaluationError, NetworkError, TimelineError, TimelineErrorKind};
+
+use crate::environment::{ContextMap, Variables};
+use crate::chip::Chip;
+use crate::network::Network;
+
+/// Sets a data field to a value just before the given tick runs.
+/// Ticks count from 0, the first line a chip executes.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Stimulus
+{
+    pub tick: u64,
+    pub field: String,
+    #[serde(with = "json_value")]
+    pub value: LiteralValue
+}
+
+/// Inputs to drive data fields over time, and the fields to record after every tick.
+///
+/// As text, a timeline looks like this, with `//` comments:
+/// ```text
+/// record :Lamp :Thrust
+/// 5 :Button = 1
+/// 12 :Fuel = 0.3
+/// 20 :Display = "hello"
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Timeline
+{
+    pub stimuli: Vec<Stimulus>,
+    /// Fields to record after each tick. If it's empty, every data field is recorded.
+    #[serde(default)]
+    pub record: Vec<String>
+}
+
+/// The recorded data fields after one tick
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TickRecord
+{
+    pub tick: u64,
+    pub fields: BTreeMap<String, LiteralValue>
+}
+
+impl fmt::Display for TickRecord
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+    {
+        write!(f, "tick {}:", self.tick)?;
+        for (field, value) in &self.fields
+        {
+            write!(f, " {}={}", field, value)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Timeline
+{
+    pub fn new() -> Timeline
+    {
+        Timeline::default()
+    }
+
+    pub fn from_json(input: &str) -> Result<Timeline, TimelineError>
+    {
+        serde_json::from_str(input)
+            .map_err(|error| TimelineError::new(error.line(), TimelineErrorKind::Json(error.to_string())))
+    }
+
+    pub fn from_text(input: &str) -> Result<Timeline, TimelineError>
+    {
+        let mut timeline = Timeline::new();
+
+        for (index, line) in input.lines().enumerate()
+        {
+            let line_number = index + 1;
+            let line = match line.find("//")
+            {
+                Some(comment) => &line[..comment],
+                None => line
+            }.trim();
+
+            if line.is_empty()
+            {
+                continue;
+            }
+
+            if let Some(fields) = line.strip_prefix("record ")
+            {
+                timeline.record.extend(fields.split_whitespace().map(String::from));
+                continue;
+            }
+
+            let stimulus = parse_stimulus(line).map_err(|kind| TimelineError::new(line_number, kind))?;
+            timeline.stimuli.push(stimulus);
+        }
+
+        Ok(timeline)
+    }
+
+    /// Adds a stimulus to the timeline
+    pub fn at(mut self, tick: u64, field: &str, value: LiteralValue) -> Timeline
+    {
+        self.stimuli.push(Stimulus {
+            tick,
+            field: String::from(field),
+            value
+        });
+
+        self
+    }
+
+    /// Sets every field the timeline has for the tick.
+    /// Stimuli on the same tick are applied in the order they were written.
+    pub fn apply<C: ContextMap>(&self, tick: u64, context: &mut C)
+    {
+        for stimulus in self.stimuli.iter().filter(|stimulus| stimulus.tick == tick)
+        {
+            context.set_val(stimulus.field.clone(), stimulus.value.clone());
+        }
+    }
+
+    // Picks out the fields to record from all the data fields.
+    // Fields are recorded by the spelling they were first set with.
+    fn record_fields(&self, tick: u64, all_fields: &Variables) -> TickRecord
+    {
+        let fields = if self.record.is_empty()
+        {
+            all_fields.iter()
+                .map(|(field, value)| (String::from(field), value.clone()))
+                .collect()
+        }
+        else
+        {
+            self.record.iter()
+                .map(|field| {
+                    let name = all_fields.name(field).unwrap_or(field);
+                    let value = all_fields.get(field).cloned().unwrap_or_else(LiteralValue::get_false);
+
+                    (String::from(name), value)
+                })
+                .collect()
+        };
+
+        TickRecord {
+            tick,
+            fields
+        }
+    }
+
+    /// Runs the chip for `ticks` ticks, applying the timeline before each one.
+    /// Returns the recorded fields after every tick.
+    pub fn run_chip(&self, chip: &mut Chip, ticks: u64) -> Result<Vec<TickRecord>, EvaluationError>
+    {
+        let mut records = Vec::new();
+
+        for _ in 0..ticks
+        {
+            let tick = chip.ticks();
+
+            self.apply(tick, &mut chip.env);
+            chip.step()?;
+
+            records.push(self.record_fields(tick, chip.env.global_context()));
+        }
+
+        Ok(records)
+    }
+
+    /// Runs the network for `ticks` ticks, applying the timeline to its bus before each one.
+    /// Returns the recorded fields after every tick.
+    pub fn run_network(&self, network: &mut Network, ticks: u64) -> Result<Vec<TickRecord>, NetworkError>
+    {
+        let mut records = Vec::new();
+
+        for _ in 0..ticks
+        {
+            let tick = network.ticks();
+
+            self.apply(tick, network);
+            network.step()?;
+
+            records.push(self.record_fields(tick, network.data_fields()));
+        }
+
+        Ok(records)
+    }
+}
+
+// Parses a line like `12 :Fuel = 0.3`
+fn parse_stimulus(line: &str) -> Result<Stimulus, TimelineErrorKind>
+{
+    let (tick, rest) = match line.split_once(char::is_whitespace)
+    {
+        Some(split) => split,
+        None => return Err(TimelineErrorKind::InvalidStimulus(String::from(line)))
+    };
+
+    let tick = tick.parse::<u64>()
+        .map_err(|_| TimelineErrorKind::InvalidTick(String::from(tick)))?;
+
+    let (field, value) = match rest.split_once('=')
+    {
+        Some((field, value)) => (field.trim(), value.trim()),
+        None => return Err(TimelineErrorKind::InvalidStimulus(String::from(line)))
+    };
+
+    if !field.starts_with(':') || field.len() < 2
+    {
+        return Err(TimelineErrorKind::InvalidField(String::from(field)));
+    }
+
+    Ok(Stimulus {
+        tick,
+        field: String::from(field),
+        value: parse_value(value)?
+    })
+}
+
+fn parse_value(input: &str) -> Result<LiteralValue, TimelineErrorKind>
+{
+    if let Some(string) = input.strip_prefix('"').and_then(|input| input.strip_suffix('"'))
+    {
+        return Ok(LiteralValue::StringVal(String::from(string)));
+    }
+
+    // Unlike yolol literals, this also takes a sign, so negative values can be written directly
+    match input.parse::<YololNumber>()
+    {
+        Ok(num) => Ok(LiteralValue::NumberVal(num)),
+        Err(_) => Err(TimelineErrorKind::InvalidValue(String::from(input)))
+    }
+}
+
+// Lets json timelines write values as plain numbers and strings, like `"value": 0.3`
+pub(crate) mod json_value
+{
+    use serde::{Serialize, Deserialize, Serializer, Deserializer};
+    use serde::de::Error;
+
+    use crate::types::ast::value::LiteralValue;
+
+    pub fn serialize<S: Serializer>(value: &LiteralValue, serializer: S) -> Result<S::Ok, S::Error>
+    {
+        match value
+        {
+            LiteralValue::NumberVal(num) => num.float_value().serialize(serializer),
+            LiteralValue::StringVal(string) => string.serialize(serializer)
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<LiteralValue, D::Error>
+    {
+        match serde_json::Value::deserialize(deserializer)?
+        {
+            serde_json::Value::String(string) => Ok(LiteralValue::StringVal(string)),
+            // Going through the float would lose the exact decimal that was written
+            serde_json::Value::Number(num) => {
+                super::parse_value(&num.to_string())
+                    .map_err(|_| D::Error::custom(format!("'{}' isn't a valid yolol number", num)))
+            },
+            other => Err(D::Error::custom(format!("expected a number or a string, found {}", other)))
+        }
+    }
+
+    /// The same, for maps of names to values like `{ "a": 1, ":door": "open" }`
+    pub mod map
+    {
+        use std::collections::BTreeMap;
+
+        use serde::{Deserialize, Serializer, Deserializer};
+        use serde::ser::SerializeMap;
+
+        use crate::types::ast::value::LiteralValue;
+
+        pub fn serialize<S: Serializer>(values: &BTreeMap<String, LiteralValue>, serializer: S) -> Result<S::Ok, S::Error>
+        {
+            // Wraps a value so it serializes through the parent module
+            struct Value<'a>(&'a LiteralValue);
+
+            impl serde::Serialize for Value<'_>
+            {
+                fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error>
+                {
+                    super::serialize(self.0, serializer)
+                }
+            }
+
+            let mut map = serializer.serialize_map(Some(values.len()))?;
+            for (name, value) in values
+            {
+                map.serialize_entry(name, &Value(value))?;
+            }
+
+            map.end()
+        }
+
+        pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<BTreeMap<String, LiteralValue>, D::Error>
+        {
+            BTreeMap::<String, serde_json::Value>::deserialize(deserializer)?
+                .into_iter()
+                .map(|(name, value)| Ok((name, super::deserialize(value).map_err(serde::de::Error::custom)?)))
+                .collect()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    fn number(source: &str) -> LiteralValue
+    {
+        LiteralValue::NumberVal(source.parse().unwrap())
+    }
+
+    #[test]
+    fn text_timelines_have_stimuli_and_fields_to_record()
+    {
+        let timeline = Timeline::from_text("\
+            // a comment\n\
+            record :Lamp :Thrust\n\
+            \n\
+            5 :Button = 1 // pressed\n\
+            12 :Fuel = -0.3\n\
+            20 :Display = \"hello\"\n").unwrap();
+
+        assert_eq!(timeline.record, [":Lamp", ":Thrust"]);
+        assert_eq!(timeline.stimuli, Timeline::new()
+            .at(5, ":Button", LiteralValue::from(1))
+            .at(12, ":Fuel", number("-0.3"))
+            .at(20, ":Display", LiteralValue::from("hello"))
+            .stimuli);
+    }
+
+    #[test]
+    fn json_timelines_take_plain_numbers_and_strings()
+    {
+        let timeline = Timeline::from_json(r#"{
+            "stimuli": [
+                { "tick": 5, "field": ":Button", "value": 1 },
+                { "tick": 12, "field": ":Fuel", "value": 0.3 },
+                { "tick": 20, "field": ":Display", "value": "hello" }
+            ]
+        }"#).unwrap();
+
+        assert!(timeline.record.is_empty());
+        assert_eq!(timeline, Timeline::new()
+            .at(5, ":Button", LiteralValue::from(1))
+            .at(12, ":Fuel", number("0.3"))
+            .at(20, ":Display", LiteralValue::from("hello")));
+
+        let json = serde_json::to_string(&timeline).unwrap();
+        assert_eq!(Timeline::from_json(&json).unwrap(), timeline);
+    }
+
+    #[test]
+    fn bad_lines_are_errors_with_their_line()
+    {
+        let error = |input: &str| Timeline::from_text(input).unwrap_err();
+
+        assert_eq!(error("record :a\n5"), TimelineError::new(2, TimelineErrorKind::InvalidStimulus(String::from("5"))));
+        assert_eq!(error("5 :a 1"), TimelineError::new(1, TimelineErrorKind::InvalidStimulus(String::from("5 :a 1"))));
+        assert_eq!(error("x :a = 1"), TimelineError::new(1, TimelineErrorKind::InvalidTick(String::from("x"))));
+        assert_eq!(error("5 a = 1"), TimelineError::new(1, TimelineErrorKind::InvalidField(String::from("a"))));
+        assert_eq!(error("5 :a = one"), TimelineError::new(1, TimelineErrorKind::InvalidValue(String::from("one"))));
+    }
+
+    #[test]
+    fn bad_json_is_an_error()
+    {
+        let error = Timeline::from_json("{\n\"stimuli\": [{ \"tick\": 1, \"field\": \":a\", \"value\": true }]\n}").unwrap_err();
+
+        assert_eq!(error.line, 2);
+        assert!(matches!(error.kind, TimelineErrorKind::Json(_)));
+    }
+
+    #[test]
+    fn stimuli_are_applied_just_before_their_tick()
+    {
+        let mut chip = Chip::from_source("test", String::from(":Out = :In goto 1\n")).unwrap();
+        let timeline = Timeline::new()
+            .at(2, ":in", LiteralValue::from(1))
+            .at(4, ":IN", LiteralValue::from(2));
+
+        let records = timeline.run_chip(&mut chip, 5).unwrap();
+        let outs: Vec<(u64, LiteralValue)> = records.iter()
+            .map(|record| (record.tick, record.fields[":Out"].clone()))
+            .collect();
+
+        assert_eq!(outs, [
+            (0, LiteralValue::from(0)),
+            (1, LiteralValue::from(0)),
+            (2, LiteralValue::from(1)),
+            (3, LiteralValue::from(1)),
+            (4, LiteralValue::from(2))
+        ]);
+    }
+
+    #[test]
+    fn records_keep_the_fields_first_spelling()
+    {
+        let mut chip = Chip::from_source("test", String::from(":Lamp = 1 :other = 2\n")).unwrap();
+        let timeline = Timeline::from_text("record :LAMP :missing").unwrap();
+
+        let records = timeline.run_chip(&mut chip, 1).unwrap();
+
+        assert_eq!(records[0].to_string(), "tick 0: :Lamp=1 :missing=0");
+    }
+}
@@ -1,19 +1,28 @@
+use log::trace;
+
 use yolol_number::YololNumber;
 
 use crate::types::Token;
+use crate::types::Span;
+use crate::types::Spanned;
+use crate::types::TokenizeError;
+use crate::types::TokenizeErrorKind;
 
 use crate::types::SlidingWindow;
 use crate::types::VecWindow;
 
-pub fn tokenize(input: String) -> Result<Vec<Token>, String>
+pub fn tokenize(input: String) -> Result<Vec<Spanned<Token>>, TokenizeError>
 {
-    let mut output_vec: Vec<Token> = Vec::new();
+    let positions = char_positions(&input);
+
+    let mut output_vec: Vec<Spanned<Token>> = Vec::new();
     let mut window: VecWindow<char> = input.chars().collect();
 
     while window.remaining_length() > 0
     {
+        let start = window.index();
         let value_tuple = (window.get_value(0), window.get_value(1));
-        if cfg!(debug_assertions) { println!("[Tokenize] Matching slice: {:?}", value_tuple) }
+        trace!("Matching slice: {:?}", value_tuple);
 
         let (token, advance) = match value_tuple
         {
@@ -32,11 +41,27 @@ pub fn tokenize(input: String) -> Result<Vec<Token>, String>
             (Some(':'), Some('A'..='Z'))    => (extend_datafield(&mut window), 0),
 
             // String. Starts with a quote then extends all normal ascii chars until another quote
-            (Some('"'), Some(' '..='~'))    => (extend_string(&mut window), 0),
+            (Some('"'), _)                  => {
+                let token = extend_string(&mut window)
+                    .map_err(|kind| {
+                        // Bad chars point at themselves, unterminated strings cover the whole string
+                        let span = match kind
+                        {
+                            TokenizeErrorKind::UnexpectedChar(_) => span_between(&positions, window.index(), window.index() + 1),
+                            _ => span_between(&positions, start, window.index())
+                        };
+                        TokenizeError::new(kind, span)
+                    })?;
+                (token, 0)
+            },
 
             // YololNumber. Starts with a number extends through all other numbers
             // Will match on periods so it can represent the YololNumber decimals
-            (Some('0'..='9'), _)            => (extend_yololnum(&mut window), 0),
+            (Some('0'..='9'), _)            => {
+                let token = extend_yololnum(&mut window)
+                    .map_err(|kind| TokenizeError::new(kind, span_between(&positions, start, window.index())))?;
+                (token, 0)
+            },
             
             // Newline. Matches on CRLF or LF
             (Some('\r'), Some('\n'))        => (Some(Token::Newline), 2),
@@ -59,21 +84,63 @@ pub fn tokenize(input: String) -> Result<Vec<Token>, String>
             // Ignores spaces because they don't matter
             (Some(' '), _) => (None, 1),
 
-            // Matches on anything else. Returns an error and prints the window that failed matching
-            c => return Err(format!("[Tokenize] Failure to match on {:?}", c))
+            // Matches on anything else. Returns an error pointing at the char that failed matching
+            (Some(&c), _) => {
+                let span = span_between(&positions, start, start + 1);
+                return Err(TokenizeError::new(TokenizeErrorKind::UnexpectedChar(c), span));
+            },
+
+            (None, _) => break
         };
 
+        window.move_view(advance);
+
         if let Some(tok) = token
         {
-            output_vec.push(tok);
+            let span = span_between(&positions, start, window.index());
+            output_vec.push(Spanned::new(tok, span));
         }
-
-        window.move_view(advance);
     }
 
     Ok(output_vec)
 }
 
+// Finds the line, column and byte offset of every char in the input.
+// Has an extra entry at the end so spans can reach the end of the input.
+fn char_positions(input: &str) -> Vec<(usize, usize, usize)>
+{
+    let mut positions = Vec::new();
+    let mut line = 1;
+    let mut column = 1;
+
+    for (offset, c) in input.char_indices()
+    {
+        positions.push((line, column, offset));
+
+        if c == '\n'
+        {
+            line += 1;
+            column = 1;
+        }
+        else
+        {
+            column += 1;
+        }
+    }
+
+    positions.push((line, column, input.len()));
+    positions
+}
+
+// Makes a span covering the chars from start up to, but not including, end
+fn span_between(positions: &[(usize, usize, usize)], start: usize, end: usize) -> Span
+{
+    let (line, column, offset) = positions[start];
+    let (_, _, end_offset) = positions[end];
+
+    Span::new(line, column, offset, end_offset - offset)
+}
+
 fn extend_comment(window: &mut VecWindow<char>) -> Option<Token>
 {
     let mut char_vec: Vec<char> = Vec::new();
@@ -96,11 +163,7 @@ fn extend_comment(window: &mut VecWindow<char>) -> Option<Token>
         window.move_view(1);
     }
 
-    if let Some('\n') = window.get_value(0)
-    {
-        window.move_view(1);
-    }
-
+    // The newline is left in the window so that it still ends the line
     let output: String = char_vec.into_iter().collect();
     Some(Token::Comment(output))
 }
@@ -124,9 +187,10 @@ fn extend_alphanum(window: &mut VecWindow<char>) -> Option<Token>
         window.move_view(1);
     }
 
-    let output = char_vec.into_iter().collect::<String>().to_ascii_lowercase();
+    // Keywords are case insensitive, but identifiers keep their spelling for display
+    let output = char_vec.into_iter().collect::<String>();
 
-    let token = match output.as_str()
+    let token = match output.to_ascii_lowercase().as_str()
     {
         "goto" => Token::Goto,
         
@@ -148,7 +212,7 @@ fn extend_alphanum(window: &mut VecWindow<char>) -> Option<Token>
         "or" => Token::Or,
         "and" => Token::And,
         
-        s => Token::Identifier(String::from(s))
+        _ => Token::Identifier(output)
     };
 
     Some(token)
@@ -176,11 +240,11 @@ fn extend_datafield(window: &mut VecWindow<char>) -> Option<Token>
         window.move_view(1);
     }
 
-    let output = char_vec.into_iter().collect::<String>().to_ascii_lowercase();
+    let output = char_vec.into_iter().collect::<String>();
     Some(Token::Identifier(output))
 }
 
-fn extend_string(window: &mut VecWindow<char>) -> Option<Token>
+fn extend_string(window: &mut VecWindow<char>) -> Result<Option<Token>, TokenizeErrorKind>
 {
     let mut char_vec: Vec<char> = Vec::new();
 
@@ -189,29 +253,43 @@ fn extend_string(window: &mut VecWindow<char>) -> Option<Token>
         window.move_view(1);
     }
 
+    let mut terminated = false;
+
     while window.remaining_length() > 0
     {
         match window.get_value(0)
         {
             Some('"') => {
                 window.move_view(1);
+                terminated = true;
                 break;
             }
 
             // The ascii values between space and tilde are all the regular symbolic text characters
             Some(&c @ ' '..='~') => char_vec.push(c),
 
-            _ => break
+            // A newline ends the line before the string is closed
+            Some('\r') | Some('\n') => break,
+
+            // Anything else can't be written in a string, so the window is left on it for the error span
+            Some(&c) => return Err(TokenizeErrorKind::UnexpectedChar(c)),
+
+            None => break
         };
 
         window.move_view(1);
     }
 
+    if !terminated
+    {
+        return Err(TokenizeErrorKind::UnterminatedString);
+    }
+
     let output: String = char_vec.into_iter().collect();
-    Some(Token::StringToken(output))
+    Ok(Some(Token::StringToken(output)))
 }
 
-fn extend_yololnum(window: &mut VecWindow<char>) -> Option<Token>
+fn extend_yololnum(window: &mut VecWindow<char>) -> Result<Option<Token>, TokenizeErrorKind>
 {
     let mut digits: Vec<char> = Vec::new();
 
@@ -228,10 +306,75 @@ fn extend_yololnum(window: &mut VecWindow<char>) -> Option<Token>
     }
 
     let string: String = digits.into_iter().collect();
-    let yolol_num = string.parse::<YololNumber>().unwrap();
-
-    Some(Token::YololNum(yolol_num))
+    match string.parse::<YololNumber>()
+    {
+        Ok(yolol_num) => Ok(Some(Token::YololNum(yolol_num))),
+        Err(_) => Err(TokenizeErrorKind::InvalidNumber(string))
+    }
 }
 
+#[cfg(test)]
+mod tests
+{
+    use super::*;
 
+    #[test]
+    fn non_ascii_chars_in_strings_are_unexpected()
+    {
+        let error = tokenize("a = \"héllo\"".to_owned()).unwrap_err();
 
+        assert_eq!(error.kind, TokenizeErrorKind::UnexpectedChar('é'));
+        assert_eq!(error.span, Span::new(1, 7, 6, 2));
+    }
+
+    #[test]
+    fn unclosed_strings_are_unterminated()
+    {
+        let error = tokenize("a = \"hello\nb = 1".to_owned()).unwrap_err();
+
+        assert_eq!(error.kind, TokenizeErrorKind::UnterminatedString);
+        assert_eq!(error.span, Span::new(1, 5, 4, 6));
+    }
+
+    #[test]
+    fn tokens_span_their_source()
+    {
+        let tokens = tokenize("ab = 12\r\n:c++ // hi".to_owned()).unwrap();
+        let spans: Vec<Span> = tokens.iter().map(|token| token.span).collect();
+
+        assert_eq!(spans, vec![
+            Span::new(1, 1, 0, 2),
+            Span::new(1, 4, 3, 1),
+            Span::new(1, 6, 5, 2),
+            Span::new(1, 8, 7, 2),
+            Span::new(2, 1, 9, 2),
+            Span::new(2, 3, 11, 1),
+            Span::new(2, 4, 12, 1),
+            Span::new(2, 6, 14, 5)
+        ]);
+    }
+
+    #[test]
+    fn spans_count_chars_not_bytes()
+    {
+        let tokens = tokenize("// é\na".to_owned()).unwrap();
+
+        assert_eq!(tokens[2].node, Token::Identifier(String::from("a")));
+        assert_eq!(tokens[2].span, Span::new(2, 1, 6, 1));
+    }
+
+    #[test]
+    fn keywords_ignore_case_but_names_keep_theirs()
+    {
+        let tokens: Vec<Token> = tokenize("IF Abc Then GoTo 1 END".to_owned()).unwrap()
+            .into_iter()
+            .map(|token| token.node)
+            .collect();
+
+        assert_eq!(tokens[0], Token::If);
+        assert_eq!(tokens[1], Token::Identifier(String::from("Abc")));
+        assert_eq!(tokens[2], Token::Then);
+        assert_eq!(tokens[3], Token::Goto);
+        assert_eq!(tokens[5], Token::End);
+    }
+}
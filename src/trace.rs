@@ -0,0 +1,271 @@
+use std::fmt;
+use std::collections::BTreeMap;
+
+use serde::{Serialize, Deserialize};
+
+use crate::types::ast::value::LiteralValue;
+use crate::types::EvaluationError;
+
+use crate::environment::normalize_ident;
+
+/// A variable a statement read, and the value it had
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VarRead
+{
+    pub name: String,
+    pub value: LiteralValue
+}
+
+/// A variable a statement wrote, with its value before and after
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VarWrite
+{
+    pub name: String,
+    pub old: LiteralValue,
+    pub new: LiteralValue
+}
+
+/// Everything one statement did
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TraceEntry
+{
+    /// How many lines had been executed since the trace started, counting from 0
+    pub tick: u64,
+    pub line: i64,
+    /// Index of the statement on its line
+    pub statement: usize,
+    /// The statement as yolol source
+    pub source: String,
+
+    pub reads: Vec<VarRead>,
+    pub writes: Vec<VarWrite>,
+    pub goto: Option<i64>,
+    pub error: Option<EvaluationError>
+}
+
+impl fmt::Display for TraceEntry
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+    {
+        write!(f, "tick {} line {}.{}: {}", self.tick, self.line, self.statement, self.source)?;
+
+        for read in &self.reads
+        {
+            write!(f, "\n    read {} = {}", read.name, read.value)?;
+        }
+
+        for write in &self.writes
+        {
+            write!(f, "\n    write {} = {} -> {}", write.name, write.old, write.new)?;
+        }
+
+        if let Some(line) = self.goto
+        {
+            write!(f, "\n    goto {}", line)?;
+        }
+
+        if let Some(error) = &self.error
+        {
+            write!(f, "\n    error {}", error)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// The first place two traces did something different
+#[derive(Debug, Clone, PartialEq)]
+pub struct TraceDivergence
+{
+    /// Index of the entry in both traces
+    pub index: usize,
+    pub left: Option<TraceEntry>,
+    pub right: Option<TraceEntry>
+}
+
+impl fmt::Display for TraceDivergence
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+    {
+        writeln!(f, "traces diverge at entry {}", self.index)?;
+
+        match &self.left
+        {
+            Some(entry) => writeln!(f, "< {}", entry)?,
+            None => writeln!(f, "< end of trace")?
+        }
+
+        match &self.right
+        {
+            Some(entry) => write!(f, "> {}", entry),
+            None => write!(f, "> end of trace")
+        }
+    }
+}
+
+/// A record of every statement a chip executed, built up by the interpreter while tracing is on
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Trace
+{
+    pub entries: Vec<TraceEntry>,
+
+    // The accesses of the statement currently being evaluated
+    #[serde(skip)]
+    reads: Vec<VarRead>,
+    #[serde(skip)]
+    writes: Vec<VarWrite>,
+    #[serde(skip)]
+    tick: u64
+}
+
+impl Trace
+{
+    pub fn new() -> Trace
+    {
+        Trace::default()
+    }
+
+    pub fn from_json(input: &str) -> Result<Trace, serde_json::Error>
+    {
+        serde_json::from_str(input)
+    }
+
+    pub fn record_read(&mut self, name: &str, value: &LiteralValue)
+    {
+        self.reads.push(VarRead {
+            name: normalize_ident(name).into_owned(),
+            value: value.clone()
+        });
+    }
+
+    pub fn record_write(&mut self, name: &str, old: LiteralValue, new: LiteralValue)
+    {
+        self.writes.push(VarWrite {
+            name: normalize_ident(name).into_owned(),
+            old,
+            new
+        });
+    }
+
+    /// Turns the accesses recorded since the last statement into an entry for this one
+    pub fn finish_statement(&mut self, line: i64, statement: usize, source: String, goto: Option<i64>, error: Option<EvaluationError>)
+    {
+        self.entries.push(TraceEntry {
+            tick: self.tick,
+            line,
+            statement,
+            source,
+            reads: self.reads.drain(..).collect(),
+            writes: self.writes.drain(..).collect(),
+            goto,
+            error
+        });
+    }
+
+    pub fn finish_line(&mut self)
+    {
+        self.tick += 1;
+    }
+
+    /// Replays the writes up to and including the given tick, returning the value of every variable written so far
+    pub fn state_at(&self, tick: u64) -> BTreeMap<String, LiteralValue>
+    {
+        let mut state = BTreeMap::new();
+
+        for entry in self.entries.iter().take_while(|entry| entry.tick <= tick)
+        {
+            for write in &entry.writes
+            {
+                state.insert(write.name.clone(), write.new.clone());
+            }
+        }
+
+        state
+    }
+
+    /// Finds the first entry where the traces differ, or None if they're the same
+    pub fn diff(&self, other: &Trace) -> Option<TraceDivergence>
+    {
+        let length = self.entries.len().max(other.entries.len());
+
+        (0..length)
+            .find(|&index| self.entries.get(index) != other.entries.get(index))
+            .map(|index| TraceDivergence {
+                index,
+                left: self.entries.get(index).cloned(),
+                right: other.entries.get(index).cloned()
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    use crate::chip::Chip;
+    use crate::environment::ErrorMode;
+
+    fn trace(source: &str, ticks: u64) -> Trace
+    {
+        let mut chip = Chip::from_source("test", String::from(source)).unwrap();
+        chip.env.error_mode = ErrorMode::AbortLine;
+        chip.env.start_trace();
+        chip.run_ticks(ticks).unwrap();
+
+        chip.env.take_trace().unwrap()
+    }
+
+    #[test]
+    fn same_runs_dont_diverge()
+    {
+        let source = "a++ :B = a\ngoto 1\n";
+        assert_eq!(trace(source, 4).diff(&trace(source, 4)), None);
+    }
+
+    #[test]
+    fn diverging_runs_point_at_the_first_different_statement()
+    {
+        let left = trace("a++ :b = a\nb = 1 goto 1\n", 2);
+        let right = trace("a++ :b = a\nb = 2 goto 1\n", 2);
+
+        let divergence = left.diff(&right).unwrap();
+
+        assert_eq!(divergence.index, 2);
+        assert_eq!(divergence.left.unwrap().writes[0].new, LiteralValue::from(1));
+        assert_eq!(divergence.right.unwrap().writes[0].new, LiteralValue::from(2));
+    }
+
+    #[test]
+    fn shorter_traces_diverge_where_they_end()
+    {
+        let source = "a++ goto 1\n";
+        let divergence = trace(source, 2).diff(&trace(source, 3)).unwrap();
+
+        assert_eq!(divergence.index, 4);
+        assert!(divergence.left.is_none());
+        assert_eq!(divergence.right.unwrap().tick, 2);
+    }
+
+    #[test]
+    fn state_replays_writes_up_to_the_tick()
+    {
+        let trace = trace("a = 1 :B = 2\na = \"x\"\n", 2);
+
+        let state = trace.state_at(0);
+        assert_eq!(state.get("a"), Some(&LiteralValue::from(1)));
+        assert_eq!(state.get(":b"), Some(&LiteralValue::from(2)));
+
+        assert_eq!(trace.state_at(1).get("a"), Some(&LiteralValue::from("x")));
+    }
+
+    #[test]
+    fn traces_survive_json()
+    {
+        let trace = trace("a = 1 b = a / 0\n", 1);
+        let json = serde_json::to_string(&trace).unwrap();
+
+        assert!(trace.entries[1].error.is_some());
+        assert_eq!(Trace::from_json(&json).unwrap().diff(&trace), None);
+    }
+}
@@ -12,6 +12,8 @@ use cylon_ast::{
     boxed_try_from_impl,
 };
 
+use crate::types::{Spanned, Ident};
+
 use super::{
     program::Program        as AstProgram,
     line::Line              as AstLine,
@@ -21,13 +23,53 @@ use super::{
     operators::Operator     as Op,
 };
 
+// The macros can't parse a nested generic type
+type SpannedExpr = Spanned<AstExpr>;
+
 boxed_from_impl! {
-    From<Box<AstExpr>> for Box<CylonExpr>
+    From<Box<AstExpr>> for Box<CylonExpr>;
+    From<Box<SpannedExpr>> for Box<CylonExpr>
 }
 
 boxed_try_from_impl! {
     TryFrom<Box<CylonStat>> for Box<AstStat>;
-    TryFrom<Box<CylonExpr>> for Box<AstExpr>
+    TryFrom<Box<CylonExpr>> for Box<AstExpr>;
+    TryFrom<Box<CylonExpr>> for Box<SpannedExpr>
+}
+
+// Cylon has no spans, so they're dropped going to it and left empty coming back
+impl From<Spanned<AstStat>> for CylonStat
+{
+    fn from(stat: Spanned<AstStat>) -> CylonStat
+    {
+        stat.node.into()
+    }
+}
+
+impl TryFrom<CylonStat> for Spanned<AstStat>
+{
+    type Error = String;
+    fn try_from(stat: CylonStat) -> Result<Self, Self::Error>
+    {
+        AstStat::try_from(stat).map(Spanned::from)
+    }
+}
+
+impl From<Spanned<AstExpr>> for CylonExpr
+{
+    fn from(expr: Spanned<AstExpr>) -> CylonExpr
+    {
+        expr.node.into()
+    }
+}
+
+impl TryFrom<CylonExpr> for Spanned<AstExpr>
+{
+    type Error = String;
+    fn try_from(expr: CylonExpr) -> Result<Self, Self::Error>
+    {
+        AstExpr::try_from(expr).map(Spanned::from)
+    }
 }
 
 impl From<AstProgram> for CylonProg
@@ -53,7 +95,7 @@ impl TryFrom<CylonProg> for AstProgram
             ast_program.push(line.try_into()?);
         }
 
-        Ok(AstProgram(ast_program))
+        Ok(AstProgram::new(ast_program))
     }
 }
 
@@ -74,13 +116,13 @@ impl From<AstLine> for CylonLine
 
         for stat in line.0
         {
-            match stat
+            match stat.node
             {
                 AstStat::Comment(string) => {
                     comment = Some(string);
                 }
 
-                _ => {
+                stat => {
                     output.push(stat.into());
                 }
             }
@@ -104,13 +146,13 @@ impl TryFrom<CylonLine> for AstLine
         //     ast_line.push(stat.try_into()?);
         // }
 
-        let mut ast_line: Vec<AstStat> = line.code.into_iter()
-            .map(|s| s.try_into())
+        let mut ast_line: Vec<Spanned<AstStat>> = line.code.into_iter()
+            .map(|s| AstStat::try_from(s).map(Spanned::from))
             .collect::<Result<_, Self::Error>>()?;
 
         if !line.comment.is_empty()
         {
-            ast_line.push(AstStat::Comment(line.comment));
+            ast_line.push(Spanned::from(AstStat::Comment(line.comment)));
         }
 
         Ok(AstLine(ast_line))
@@ -184,11 +226,11 @@ impl TryFrom<CylonStat> for AstStat
             CylonStat::If { condition, body, else_body } => {
                 let cond = Box::new(condition.try_into()?);
 
-                let ast_body: Vec<AstStat> = body.into_iter()
+                let ast_body: Vec<Spanned<AstStat>> = body.into_iter()
                     .map(|s| s.try_into())
                     .collect::<Result<_, Self::Error>>()?;
 
-                let ast_else_body: Vec<AstStat> = else_body.into_iter()
+                let ast_else_body: Vec<Spanned<AstStat>> = else_body.into_iter()
                     .map(|s| s.try_into())
                     .collect::<Result<_, Self::Error>>()?;
 
@@ -204,11 +246,11 @@ impl TryFrom<CylonStat> for AstStat
             CylonStat::Assignment { identifier, operator, value } => {
                 let ident = if identifier.starts_with(':')
                 {
-                    AstValue::DataField(identifier)
+                    AstValue::DataField(Ident::new(&identifier))
                 }
                 else
                 {
-                    AstValue::LocalVar(identifier)
+                    AstValue::LocalVar(Ident::new(&identifier))
                 };
 
                 let op = match operator.as_str()
@@ -219,6 +261,7 @@ impl TryFrom<CylonStat> for AstStat
                     "*=" => Op::MulAssign,
                     "/=" => Op::DivAssign,
                     "%=" => Op::ModAssign,
+                    "^=" => Op::PowAssign,
 
                     bad_op => return Err(format!("[Statement::TryFrom<CylonStat>] Unable to convert to assignment op from string! Found '{}'", bad_op))
                 };
@@ -346,11 +389,11 @@ impl TryFrom<CylonExpr> for AstExpr
             CylonExpr::Identifier { name } => {
                 let value = if name.starts_with(':')
                 {
-                    AstValue::DataField(name)
+                    AstValue::DataField(Ident::new(&name))
                 }
                 else
                 {
-                    AstValue::LocalVar(name)
+                    AstValue::LocalVar(Ident::new(&name))
                 };
 
                 Ok(AstExpr::Value(value))
@@ -374,7 +417,7 @@ impl From<AstValue> for CylonExpr
             AstValue::LocalVar(ident) |
             AstValue::DataField(ident) => {
                 CylonExpr::Identifier {
-                    name: ident
+                    name: ident.name
                 }
             },
 
@@ -392,3 +435,25 @@ impl From<AstValue> for CylonExpr
         }
     }
 }
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    use crate::tokenizer;
+    use crate::parser;
+    use crate::types::TokenWindow;
+
+    #[test]
+    fn pow_assign_round_trips_through_cylon()
+    {
+        let tokens = tokenizer::tokenize(String::from("a ^= 2 :b ^= a + 1\n")).unwrap();
+        let program = parser::parse_program(&mut TokenWindow::from(tokens)).unwrap();
+
+        let cylon = CylonProg::from(program.clone());
+        let round_tripped = AstProgram::try_from(cylon).unwrap();
+
+        assert_eq!(round_tripped.0[0].0, program.0[0].0);
+    }
+}
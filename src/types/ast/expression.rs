@@ -1,15 +1,20 @@
 use std::fmt;
 
+use serde::{Serialize, Deserialize};
+
 use crate::types::ast::{
     operators::Operator,
     value::Value,
 };
 
-#[derive(Debug, PartialEq, Clone)]
+use crate::types::Spanned;
+
+/// An expression, with every operand spanned so errors can point at the part of the expression that failed
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub enum Expression
 {
-    BinaryOp(Operator, Box<Expression>, Box<Expression>),
-    UnaryOp(Operator, Box<Expression>),
+    BinaryOp(Operator, Box<Spanned<Expression>>, Box<Spanned<Expression>>),
+    UnaryOp(Operator, Box<Spanned<Expression>>),
     Value(Value)
 }
 
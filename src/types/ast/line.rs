@@ -1,9 +1,12 @@
 use std::fmt;
 
+use serde::{Serialize, Deserialize};
+
 use crate::types::ast::statement::Statement;
+use crate::types::Spanned;
 
-#[derive(Debug, Clone)]
-pub struct Line(pub Vec<Statement>);
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Line(pub Vec<Spanned<Statement>>);
 
 impl fmt::Display for Line
 {
@@ -1,9 +1,11 @@
 use std::fmt;
 use std::error;
 
+use serde::{Serialize, Deserialize};
+
 use crate::types::ast::value::LiteralValue;
 
-#[derive(Debug, PartialEq, PartialOrd, Clone, Copy)]
+#[derive(Debug, PartialEq, PartialOrd, Clone, Copy, Serialize, Deserialize)]
 pub enum Operator
 {
     Assign,
@@ -12,6 +14,7 @@ pub enum Operator
     MulAssign,
     DivAssign,
     ModAssign,
+    PowAssign,
 
     Negate,
     PreInc,
@@ -59,6 +62,7 @@ impl fmt::Display for Operator
             Operator::MulAssign => "*=",
             Operator::DivAssign => "/=",
             Operator::ModAssign => "%=",
+            Operator::PowAssign => "^=",
 
             Operator::Negate => "-",
             Operator::PreInc => "++",
@@ -152,24 +156,37 @@ impl fmt::Display for Operator
 //     }
 // }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize)]
+pub enum OperatorErrorKind
+{
+    /// The operator can't be used on the types of values it was given
+    TypeMismatch,
+    /// The input is outside of the range the operator is defined for
+    DomainError,
+    DivisionByZero,
+    /// Tried to decrement a string that has no characters left to remove
+    EmptyString,
+    Unimplemented
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub struct OperatorError
 {
     pub op: Operator,
     pub left: Option<LiteralValue>,
     pub right: Option<LiteralValue>,
-    pub error_text: String
+    pub kind: OperatorErrorKind
 }
 
 impl OperatorError
 {
-    pub fn new(op: Operator, left: Option<LiteralValue>, right: Option<LiteralValue>, error_text: String) -> OperatorError
+    pub fn new(op: Operator, left: Option<LiteralValue>, right: Option<LiteralValue>, kind: OperatorErrorKind) -> OperatorError
     {
         OperatorError {
             op,
             left,
             right,
-            error_text
+            kind
         }
     }
 }
@@ -178,8 +195,25 @@ impl fmt::Display for OperatorError
 {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
     {
-        write!(f, "[OPERATOR ERROR] Op: {:?}, left: {:?}, right: {:?}. Error text: {}",
-            self.op, self.left, self.right, self.error_text)
+        let operands = match (&self.left, &self.right)
+        {
+            (Some(left), Some(right)) => format!("{} {} {}", left, self.op, right),
+            (Some(operand), None) if self.op == Operator::Fact => format!("{}{}", operand, self.op),
+            (Some(operand), None) |
+            (None, Some(operand)) => format!("{} {}", self.op, operand),
+            (None, None) => format!("{}", self.op)
+        };
+
+        let reason = match self.kind
+        {
+            OperatorErrorKind::TypeMismatch => "the operator doesn't work on these types",
+            OperatorErrorKind::DomainError => "the input is out of range",
+            OperatorErrorKind::DivisionByZero => "division by zero",
+            OperatorErrorKind::EmptyString => "the string is empty",
+            OperatorErrorKind::Unimplemented => "the operator isn't implemented",
+        };
+
+        write!(f, "'{}' failed: {}", operands, reason)
     }
 }
 
@@ -189,4 +223,4 @@ impl error::Error for OperatorError
     {
         None
     }
-}
\ No newline at end of file
+}
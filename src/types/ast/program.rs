@@ -1,9 +1,100 @@
 use std::fmt;
 
-use super::line::Line;
+use serde::{Serialize, Deserialize, Serializer, Deserializer};
 
+use super::{
+    line::Line,
+    statement::Statement,
+    expression::Expression,
+    value::Value,
+};
+use crate::types::SymbolTable;
+
+/// The lines of a program, along with the symbol table of its local variables
 #[derive(Debug, Clone)]
-pub struct Program(pub Vec<Line>);
+pub struct Program(pub Vec<Line>, SymbolTable);
+
+impl Program
+{
+    /// Interns the names of the locals in the lines into a new symbol table
+    pub fn new(mut lines: Vec<Line>) -> Program
+    {
+        let mut symbols = SymbolTable::new();
+
+        for statement in lines.iter_mut().flat_map(|line| line.0.iter_mut())
+        {
+            intern_statement(statement, &mut symbols);
+        }
+
+        Program(lines, symbols)
+    }
+
+    pub fn symbols(&self) -> &SymbolTable
+    {
+        &self.1
+    }
+}
+
+fn intern_statement(statement: &mut Statement, symbols: &mut SymbolTable)
+{
+    match statement
+    {
+        Statement::Comment(_) => {},
+        Statement::If(cond, body, else_body) => {
+            intern_expression(cond, symbols);
+            for statement in body.iter_mut().chain(else_body.iter_mut().flatten())
+            {
+                intern_statement(statement, symbols);
+            }
+        },
+        Statement::Goto(expr) |
+        Statement::Expression(expr) => intern_expression(expr, symbols),
+        Statement::Assignment(value, _, expr) => {
+            intern_value(value, symbols);
+            intern_expression(expr, symbols);
+        }
+    }
+}
+
+fn intern_expression(expr: &mut Expression, symbols: &mut SymbolTable)
+{
+    match expr
+    {
+        Expression::BinaryOp(_, left, right) => {
+            intern_expression(left, symbols);
+            intern_expression(right, symbols);
+        },
+        Expression::UnaryOp(_, operand) => intern_expression(operand, symbols),
+        Expression::Value(value) => intern_value(value, symbols)
+    }
+}
+
+fn intern_value(value: &mut Value, symbols: &mut SymbolTable)
+{
+    match value
+    {
+        Value::Group(expr) => intern_expression(expr, symbols),
+        Value::LocalVar(ident) => ident.symbol = Some(symbols.intern(&ident.name)),
+        _ => {}
+    }
+}
+
+// Only the lines are serialized, the symbol table is rebuilt from them
+impl Serialize for Program
+{
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    {
+        self.0.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Program
+{
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Program, D::Error>
+    {
+        Ok(Program::new(Vec::deserialize(deserializer)?))
+    }
+}
 
 impl fmt::Display for Program
 {
@@ -1,19 +1,23 @@
 use std::fmt;
 
+use serde::{Serialize, Deserialize};
+
 use crate::types::ast::{
     expression::Expression,
     operators::Operator,
     value::Value,
 };
 
-#[derive(Debug, PartialEq, Clone)]
+use crate::types::Spanned;
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub enum Statement
 {
     Comment(String),
-    If(Box<Expression>, Vec<Statement>, Option<Vec<Statement>>),
-    Goto(Box<Expression>),
-    Assignment(Value, Operator, Box<Expression>),
-    Expression(Box<Expression>)
+    If(Box<Spanned<Expression>>, Vec<Spanned<Statement>>, Option<Vec<Spanned<Statement>>>),
+    Goto(Box<Spanned<Expression>>),
+    Assignment(Value, Operator, Box<Spanned<Expression>>),
+    Expression(Box<Spanned<Expression>>)
 }
 
 impl fmt::Display for Statement
@@ -1,21 +1,25 @@
 use std::fmt;
 
+use serde::{Serialize, Deserialize};
+
 use yolol_number::YololNumber;
 
 use crate::types::{
     ast::expression::Expression,
     Token,
+    Spanned,
+    Ident,
 };
 
 mod literal_value;
 pub use literal_value::*;
 
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub enum Value
 {
-    Group(Box<Expression>),
-    LocalVar(String),
-    DataField(String),
+    Group(Box<Spanned<Expression>>),
+    LocalVar(Ident),
+    DataField(Ident),
     NumberVal(YololNumber),
     StringVal(String)
 }
@@ -27,8 +31,8 @@ impl fmt::Display for Value
         match self
         {
             Value::Group(expr) => write!(f, "({})", *expr),
-            Value::LocalVar(string) => write!(f, "{}", string),
-            Value::DataField(string) => write!(f, "{}", string),
+            Value::LocalVar(ident) => write!(f, "{}", ident),
+            Value::DataField(ident) => write!(f, "{}", ident),
             Value::NumberVal(num) => write!(f, "{}", num),
             Value::StringVal(string) => write!(f, "\"{}\"", string),
         }
@@ -42,7 +46,8 @@ impl From<Token> for Value
         match input
         {
             Token::Identifier(ident) => {
-                if let Some(':') = ident.chars().next()
+                let ident = Ident::new(&ident);
+                if ident.is_data_field()
                 {
                     Value::DataField(ident)
                 }
@@ -7,7 +7,7 @@ use serde::{Serialize, Deserialize};
 use yolol_number::prelude::*;
 
 use crate::types::{
-    ast::operators::{Operator, OperatorError},
+    ast::operators::{Operator, OperatorError, OperatorErrorKind},
 };
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -68,14 +68,44 @@ impl LiteralValue
             },
 
             (left, right) => Err(OperatorError::new(Operator::Pow, Some(left), Some(right),
-                        String::from("Attempt to do pow operation without two number values")))
+                        OperatorErrorKind::TypeMismatch))
         }
     }
 
+    // Like in game, the input is floored and the result saturates at the max value, as any multiplication would
     pub fn factorial(self) -> Result<LiteralValue, OperatorError>
     {
-        Err(OperatorError::new(Operator::Fact, Some(self), None,
-            String::from("Factorial operation isn't implemented currently!")))
+        match self
+        {
+            LiteralValue::NumberVal(num) if num.is_negative() => {
+                Err(OperatorError::new(Operator::Fact, Some(self), None,
+                    OperatorErrorKind::DomainError))
+            },
+
+            LiteralValue::NumberVal(num) => {
+                let max = YololNumber::max_value();
+                let input: i64 = num.floor().get_value();
+
+                let mut output = YololNumber::from_value(1);
+                for factor in 2..=input
+                {
+                    output = output * YololNumber::from_value(factor);
+
+                    // Nothing will bring it back down once it's hit the max, so stop early for huge inputs
+                    if output == max
+                    {
+                        break;
+                    }
+                }
+
+                Ok(LiteralValue::NumberVal(output))
+            },
+
+            LiteralValue::StringVal(_) => {
+                Err(OperatorError::new(Operator::Fact, Some(self), None,
+                    OperatorErrorKind::TypeMismatch))
+            }
+        }
     }
 
     pub fn abs(self) -> Result<LiteralValue, OperatorError>
@@ -87,7 +117,7 @@ impl LiteralValue
         else
         {
             Err(OperatorError::new(Operator::Abs, Some(self), None,
-                String::from("Tried to abs on non-number literal value!")))
+                OperatorErrorKind::TypeMismatch))
         }
     }
 
@@ -99,7 +129,7 @@ impl LiteralValue
             if output.is_negative()
             {
                 Err(OperatorError::new(Operator::Sqrt, Some(self), None,
-                    String::from("Sqrt input was out of range!")))
+                    OperatorErrorKind::DomainError))
             }
             else
             {
@@ -109,7 +139,7 @@ impl LiteralValue
         else
         {
             Err(OperatorError::new(Operator::Sqrt, Some(self), None,
-                String::from("Tried to sqrt on non-number literal value!")))
+                OperatorErrorKind::TypeMismatch))
         }
     }
 
@@ -122,7 +152,7 @@ impl LiteralValue
         else
         {
             Err(OperatorError::new(Operator::Sin, Some(self), None,
-                String::from("Tried to sin on non-number literal value!")))
+                OperatorErrorKind::TypeMismatch))
         }
     }
 
@@ -135,7 +165,7 @@ impl LiteralValue
         else
         {
             Err(OperatorError::new(Operator::Cos, Some(self), None,
-                String::from("Tried to cos on non-number literal value!")))
+                OperatorErrorKind::TypeMismatch))
         }
     }
 
@@ -148,7 +178,7 @@ impl LiteralValue
         else
         {
             Err(OperatorError::new(Operator::Tan, Some(self), None,
-                String::from("Tried to tan on non-number literal value!")))
+                OperatorErrorKind::TypeMismatch))
         }
     }
 
@@ -160,7 +190,7 @@ impl LiteralValue
             if output.is_negative()
             {
                 Err(OperatorError::new(Operator::Arcsin, Some(self), None,
-                    String::from("Arcsin input was out of range!")))
+                    OperatorErrorKind::DomainError))
             }
             else
             {
@@ -170,7 +200,7 @@ impl LiteralValue
         else
         {
             Err(OperatorError::new(Operator::Arcsin, Some(self), None,
-                String::from("Tried to arcsin on non-number literal value!")))
+                OperatorErrorKind::TypeMismatch))
         }
     }
 
@@ -182,7 +212,7 @@ impl LiteralValue
             if output.is_negative()
             {
                 Err(OperatorError::new(Operator::Arccos, Some(self), None,
-                    String::from("Arccos input was out of range!")))
+                    OperatorErrorKind::DomainError))
             }
             else
             {
@@ -192,7 +222,7 @@ impl LiteralValue
         else
         {
             Err(OperatorError::new(Operator::Arccos, Some(self), None,
-                String::from("Tried to arccos on non-number literal value!")))
+                OperatorErrorKind::TypeMismatch))
         }
     }
 
@@ -204,7 +234,7 @@ impl LiteralValue
             if output.is_negative()
             {
                 Err(OperatorError::new(Operator::Arctan, Some(self), None,
-                    String::from("Arctan input was out of range!")))
+                    OperatorErrorKind::DomainError))
             }
             else
             {
@@ -214,7 +244,7 @@ impl LiteralValue
         else
         {
             Err(OperatorError::new(Operator::Arctan, Some(self), None,
-                String::from("Tried to arctan on non-number literal value!")))
+                OperatorErrorKind::TypeMismatch))
         }
     }
 }
@@ -381,7 +411,7 @@ impl ops::Mul<LiteralValue> for LiteralValue
             },
 
             (left, right) => Err(OperatorError::new(Operator::Mul, Some(left), Some(right),
-                        String::from("Attempt to do mul operation without two number values")))
+                        OperatorErrorKind::TypeMismatch))
         }
     }
 }
@@ -395,11 +425,11 @@ impl ops::Div<LiteralValue> for LiteralValue
         {
             (LiteralValue::NumberVal(self_num), LiteralValue::NumberVal(other_num)) => {
                 Ok(LiteralValue::NumberVal(self_num.yolol_div(other_num)
-                    .ok_or_else(|| OperatorError::new(Operator::Div, None, None, "Did bad div thing! This error needs to be made better too!".to_owned()))?))
+                    .ok_or_else(|| OperatorError::new(Operator::Div, Some(LiteralValue::NumberVal(self_num)), Some(LiteralValue::NumberVal(other_num)), OperatorErrorKind::DivisionByZero))?))
             },
 
             (left, right) => Err(OperatorError::new(Operator::Div, Some(left), Some(right),
-                        String::from("Attempt to do div operation without two number values")))
+                        OperatorErrorKind::TypeMismatch))
         }
     }
 }
@@ -416,7 +446,7 @@ impl ops::Rem<LiteralValue> for LiteralValue
             },
 
             (left, right) => Err(OperatorError::new(Operator::Mod, Some(left), Some(right),
-                        String::from("Attempt to do mod operation without two number values")))
+                        OperatorErrorKind::TypeMismatch))
         }
     }
 }
@@ -433,7 +463,7 @@ impl ops::Neg for LiteralValue
             },
 
             _ => Err(OperatorError::new(Operator::Negate, Some(self), None,
-                        String::from("Attempt to do neg operation without a number value")))
+                        OperatorErrorKind::TypeMismatch))
         }
     }
 }
@@ -453,3 +483,50 @@ impl ops::Not for LiteralValue
         }
     }
 }
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    fn number(source: &str) -> LiteralValue
+    {
+        LiteralValue::NumberVal(source.parse().unwrap())
+    }
+
+    #[test]
+    fn factorial_of_whole_numbers()
+    {
+        assert_eq!(LiteralValue::from(0).factorial(), Ok(LiteralValue::from(1)));
+        assert_eq!(LiteralValue::from(5).factorial(), Ok(LiteralValue::from(120)));
+    }
+
+    #[test]
+    fn factorial_floors_its_input()
+    {
+        assert_eq!(number("3.7").factorial(), Ok(LiteralValue::from(6)));
+    }
+
+    #[test]
+    fn factorial_of_negative_numbers_is_a_domain_error()
+    {
+        let error = LiteralValue::from(-1).factorial().unwrap_err();
+        assert_eq!(error.kind, OperatorErrorKind::DomainError);
+    }
+
+    #[test]
+    fn factorial_of_strings_is_a_type_mismatch()
+    {
+        let error = LiteralValue::from("abc").factorial().unwrap_err();
+        assert_eq!(error.kind, OperatorErrorKind::TypeMismatch);
+    }
+
+    #[test]
+    fn factorial_saturates_at_the_max_value()
+    {
+        let max = LiteralValue::NumberVal(YololNumber::max_value());
+
+        assert_eq!(LiteralValue::from(30).factorial(), Ok(max.clone()));
+        assert_eq!(LiteralValue::from(1_000_000_000_000).factorial(), Ok(max));
+    }
+}
@@ -0,0 +1,94 @@
+use serde::{Serialize, Deserialize};
+
+/// The kinds of yolol chip in the game
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChipKind
+{
+    Basic,
+    Advanced,
+    #[default]
+    Professional
+}
+
+/// The size limits a chip puts on the program it runs
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Serialize, Deserialize)]
+pub struct ChipLimits
+{
+    /// How many lines the chip has. Execution wraps back to line 1 after the last one.
+    pub max_lines: usize,
+    /// How many characters fit on a single line, not counting the line break
+    pub max_line_chars: usize
+}
+
+impl ChipLimits
+{
+    pub fn new(max_lines: usize, max_line_chars: usize) -> ChipLimits
+    {
+        ChipLimits {
+            max_lines,
+            max_line_chars
+        }
+    }
+
+    /// Returns the limits of the given kind of chip
+    pub fn for_chip(kind: ChipKind) -> ChipLimits
+    {
+        match kind
+        {
+            ChipKind::Basic => ChipLimits::new(12, 70),
+            ChipKind::Advanced => ChipLimits::new(16, 70),
+            ChipKind::Professional => ChipLimits::new(20, 70)
+        }
+    }
+
+    /// Limits that no program will hit, for parsing scripts that aren't meant for a specific chip
+    pub fn unlimited() -> ChipLimits
+    {
+        ChipLimits::new(usize::MAX, usize::MAX)
+    }
+
+    /// The number of the last line on the chip.
+    /// It's capped so it still fits in a YololNumber when the limits are unlimited.
+    pub fn last_line(&self) -> i64
+    {
+        self.max_lines.min(i32::MAX as usize) as i64
+    }
+}
+
+impl Default for ChipLimits
+{
+    fn default() -> Self
+    {
+        ChipLimits::for_chip(ChipKind::default())
+    }
+}
+
+impl From<ChipKind> for ChipLimits
+{
+    fn from(kind: ChipKind) -> Self
+    {
+        ChipLimits::for_chip(kind)
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    #[test]
+    fn chips_have_the_game_limits()
+    {
+        assert_eq!(ChipLimits::for_chip(ChipKind::Basic), ChipLimits::new(12, 70));
+        assert_eq!(ChipLimits::for_chip(ChipKind::Advanced), ChipLimits::new(16, 70));
+        assert_eq!(ChipLimits::default(), ChipLimits::new(20, 70));
+    }
+
+    #[test]
+    fn unlimited_last_line_fits_in_a_number()
+    {
+        assert_eq!(ChipLimits::unlimited().last_line(), i32::MAX as i64);
+        assert_eq!(ChipLimits::from(ChipKind::Basic).last_line(), 12);
+    }
+}
@@ -0,0 +1,380 @@
+use std::fmt;
+use std::error;
+
+use serde::{Serialize, Deserialize};
+
+use crate::types::{
+    Span,
+    Token,
+    ast::operators::{Operator, OperatorError},
+    ast::value::LiteralValue,
+};
+
+/// Any error that can occur while turning yolol source into a running program
+#[derive(Debug, Clone, PartialEq)]
+pub enum YoloxideError
+{
+    Tokenize(TokenizeError),
+    Parse(ParseError),
+    Evaluation(EvaluationError)
+}
+
+impl fmt::Display for YoloxideError
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+    {
+        match self
+        {
+            YoloxideError::Tokenize(error) => write!(f, "{}", error),
+            YoloxideError::Parse(error) => write!(f, "{}", error),
+            YoloxideError::Evaluation(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl error::Error for YoloxideError
+{
+    fn source(&self) -> Option<&(dyn error::Error + 'static)>
+    {
+        match self
+        {
+            YoloxideError::Tokenize(error) => Some(error),
+            YoloxideError::Parse(error) => Some(error),
+            YoloxideError::Evaluation(error) => Some(error),
+        }
+    }
+}
+
+impl From<TokenizeError> for YoloxideError
+{
+    fn from(error: TokenizeError) -> Self
+    {
+        YoloxideError::Tokenize(error)
+    }
+}
+
+impl From<ParseError> for YoloxideError
+{
+    fn from(error: ParseError) -> Self
+    {
+        YoloxideError::Parse(error)
+    }
+}
+
+impl From<EvaluationError> for YoloxideError
+{
+    fn from(error: EvaluationError) -> Self
+    {
+        YoloxideError::Evaluation(error)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TokenizeErrorKind
+{
+    /// A character that can't start any token
+    UnexpectedChar(char),
+    /// A string that hit the end of the line or input before its closing quote
+    UnterminatedString,
+    /// A number literal that couldn't be turned into a YololNumber
+    InvalidNumber(String)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TokenizeError
+{
+    pub kind: TokenizeErrorKind,
+    pub span: Span
+}
+
+impl TokenizeError
+{
+    pub fn new(kind: TokenizeErrorKind, span: Span) -> TokenizeError
+    {
+        TokenizeError {
+            kind,
+            span
+        }
+    }
+}
+
+impl fmt::Display for TokenizeError
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+    {
+        match &self.kind
+        {
+            TokenizeErrorKind::UnexpectedChar(c) => write!(f, "{}: unexpected character {:?}", self.span, c),
+            TokenizeErrorKind::UnterminatedString => write!(f, "{}: string is missing its closing quote", self.span),
+            TokenizeErrorKind::InvalidNumber(text) => write!(f, "{}: invalid number '{}'", self.span, text),
+        }
+    }
+}
+
+impl error::Error for TokenizeError
+{
+    fn source(&self) -> Option<&(dyn error::Error + 'static)>
+    {
+        None
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParseErrorKind
+{
+    /// Nothing that could start an expression was found.
+    /// The parser also uses this internally to try the next rule.
+    ExpectedExpression { found: Option<Token> },
+    /// A token was found where one of the expected tokens should have been
+    UnexpectedToken { found: Option<Token>, expected: Vec<Token> },
+
+    RepeatedElseTokens,
+    UnbalancedParenthesis,
+
+    /// A line has more characters than the chip allows
+    LineTooLong { length: usize, max: usize },
+    /// The program has more lines than the chip allows
+    TooManyLines { max: usize }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError
+{
+    pub kind: ParseErrorKind,
+    pub span: Option<Span>
+}
+
+impl ParseError
+{
+    pub fn new(kind: ParseErrorKind) -> ParseError
+    {
+        ParseError {
+            kind,
+            span: None
+        }
+    }
+
+    /// Sets the span of the error, if it doesn't already have one
+    pub fn with_span(mut self, span: Span) -> ParseError
+    {
+        self.span = self.span.or(Some(span));
+        self
+    }
+}
+
+// Describes a token the way it'd be written in an error message
+fn describe_token(token: &Option<Token>) -> String
+{
+    match token
+    {
+        Some(Token::Newline) => String::from("end of line"),
+        Some(Token::Comment(_)) => String::from("comment"),
+        Some(token) => format!("'{}'", token),
+        None => String::from("end of input")
+    }
+}
+
+impl fmt::Display for ParseError
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+    {
+        if let Some(span) = self.span
+        {
+            write!(f, "{}: ", span)?;
+        }
+
+        match &self.kind
+        {
+            ParseErrorKind::ExpectedExpression { found } => {
+                write!(f, "expected an expression, found {}", describe_token(found))
+            },
+            ParseErrorKind::UnexpectedToken { found, expected } => {
+                let expected: Vec<String> = expected.iter()
+                    .map(|token| describe_token(&Some(token.clone())))
+                    .collect();
+
+                write!(f, "expected {}, found {}", expected.join(" or "), describe_token(found))
+            },
+            ParseErrorKind::RepeatedElseTokens => write!(f, "if statement has more than one else"),
+            ParseErrorKind::UnbalancedParenthesis => write!(f, "unbalanced parenthesis"),
+            ParseErrorKind::LineTooLong { length, max } => {
+                write!(f, "line is {} characters long, the chip only allows {}", length, max)
+            },
+            ParseErrorKind::TooManyLines { max } => write!(f, "program has more than the {} lines the chip allows", max),
+        }
+    }
+}
+
+impl error::Error for ParseError
+{
+    fn source(&self) -> Option<&(dyn error::Error + 'static)>
+    {
+        None
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum EvaluationErrorKind
+{
+    /// An operator failed on the values it was given
+    Operator(Box<OperatorError>),
+    /// Goto was given a value that isn't a line number
+    InvalidGotoTarget(LiteralValue),
+    /// Assignment, increment or decrement on something that isn't a variable
+    InvalidAssignmentTarget(String),
+    /// An operator showed up somewhere the interpreter doesn't know how to evaluate it
+    UnexpectedOperator(Operator)
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EvaluationError
+{
+    pub kind: EvaluationErrorKind,
+    pub span: Option<Span>
+}
+
+impl EvaluationError
+{
+    pub fn new(kind: EvaluationErrorKind) -> EvaluationError
+    {
+        EvaluationError {
+            kind,
+            span: None
+        }
+    }
+
+    /// Sets the span of the error, if it doesn't already have one
+    pub fn with_span(mut self, span: Span) -> EvaluationError
+    {
+        self.span = self.span.or(Some(span));
+        self
+    }
+
+    /// Whether this is an error the game recovers from by skipping the rest of the line
+    pub fn is_runtime_error(&self) -> bool
+    {
+        matches!(self.kind, EvaluationErrorKind::Operator(_) | EvaluationErrorKind::InvalidGotoTarget(_))
+    }
+}
+
+impl error::Error for EvaluationError
+{
+    fn source(&self) -> Option<&(dyn error::Error + 'static)>
+    {
+        match &self.kind
+        {
+            EvaluationErrorKind::Operator(error) => Some(error.as_ref()),
+            _ => None
+        }
+    }
+}
+
+impl fmt::Display for EvaluationError
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+    {
+        if let Some(span) = self.span
+        {
+            write!(f, "{}: ", span)?;
+        }
+
+        match &self.kind
+        {
+            EvaluationErrorKind::Operator(error) => write!(f, "{}", error),
+            EvaluationErrorKind::InvalidGotoTarget(value) => write!(f, "can't goto {}, it isn't a number", value),
+            EvaluationErrorKind::InvalidAssignmentTarget(target) => write!(f, "can't assign to '{}', it isn't a variable", target),
+            EvaluationErrorKind::UnexpectedOperator(op) => write!(f, "can't evaluate the '{}' operator here", op),
+        }
+    }
+}
+
+impl From<OperatorError> for EvaluationError
+{
+    fn from(input: OperatorError) -> EvaluationError
+    {
+        EvaluationError::new(EvaluationErrorKind::Operator(Box::new(input)))
+    }
+}
+
+/// An evaluation error from one of the chips in a network
+#[derive(Debug, Clone, PartialEq)]
+pub struct NetworkError
+{
+    /// Index of the chip in the network
+    pub chip: usize,
+    pub name: String,
+    pub error: EvaluationError
+}
+
+impl fmt::Display for NetworkError
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+    {
+        write!(f, "chip '{}': {}", self.name, self.error)
+    }
+}
+
+impl error::Error for NetworkError
+{
+    fn source(&self) -> Option<&(dyn error::Error + 'static)>
+    {
+        Some(&self.error)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TimelineErrorKind
+{
+    /// A line that isn't a `record` line or a stimulus like `5 :Button = 1`
+    InvalidStimulus(String),
+    InvalidTick(String),
+    /// A field that isn't a data field, which has to start with ':'
+    InvalidField(String),
+    /// A value that isn't a number or a quoted string
+    InvalidValue(String),
+    Json(String)
+}
+
+/// An error reading a timeline, with the line it happened on
+#[derive(Debug, Clone, PartialEq)]
+pub struct TimelineError
+{
+    pub line: usize,
+    pub kind: TimelineErrorKind
+}
+
+impl TimelineError
+{
+    pub fn new(line: usize, kind: TimelineErrorKind) -> TimelineError
+    {
+        TimelineError {
+            line,
+            kind
+        }
+    }
+}
+
+impl fmt::Display for TimelineError
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+    {
+        write!(f, "{}: ", self.line)?;
+
+        match &self.kind
+        {
+            TimelineErrorKind::InvalidStimulus(line) => write!(f, "expected a stimulus like '5 :Button = 1', found '{}'", line),
+            TimelineErrorKind::InvalidTick(tick) => write!(f, "invalid tick '{}'", tick),
+            TimelineErrorKind::InvalidField(field) => write!(f, "'{}' isn't a data field", field),
+            TimelineErrorKind::InvalidValue(value) => write!(f, "'{}' isn't a number or a string", value),
+            TimelineErrorKind::Json(error) => write!(f, "invalid json: {}", error),
+        }
+    }
+}
+
+impl error::Error for TimelineError
+{
+    fn source(&self) -> Option<&(dyn error::Error + 'static)>
+    {
+        None
+    }
+}
@@ -0,0 +1,193 @@
+use std::fmt;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use serde::{Serialize, Deserialize, Serializer, Deserializer};
+
+use crate::environment::normalize_ident;
+
+/// A local variable name interned to a small number by a `SymbolTable`, so environments can keep locals in a vec instead of a map.
+/// A symbol remembers the table it came from, so it's never used to index the slots of another table.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub struct Symbol
+{
+    table: u64,
+    index: u32
+}
+
+impl Symbol
+{
+    pub fn index(self) -> usize
+    {
+        self.index as usize
+    }
+}
+
+/// The local variable names of one program, each interned to a symbol.
+/// Names are looked up by their normalized form, so `A` and `a` are the same symbol,
+/// but each symbol keeps the spelling it was first interned with.
+/// Data fields aren't interned, they stay keyed by name.
+///
+/// A clone keeps the id of the table it came from. Clones only ever add names after the ones
+/// they share, so symbols from either stay valid in both.
+#[derive(Debug, Clone)]
+pub struct SymbolTable
+{
+    id: u64,
+    indices: HashMap<String, u32>,
+    names: Vec<String>
+}
+
+impl SymbolTable
+{
+    pub fn new() -> SymbolTable
+    {
+        static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+        SymbolTable {
+            id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+            indices: HashMap::new(),
+            names: Vec::new()
+        }
+    }
+
+    pub fn intern(&mut self, name: &str) -> Symbol
+    {
+        let key = normalize_ident(name);
+        if let Some(&index) = self.indices.get(key.as_ref())
+        {
+            return self.symbol(index);
+        }
+
+        let index = self.names.len() as u32;
+        self.names.push(String::from(name));
+        self.indices.insert(key.into_owned(), index);
+
+        self.symbol(index)
+    }
+
+    /// Returns the symbol if the name has been interned already, without interning it
+    pub fn get(&self, name: &str) -> Option<Symbol>
+    {
+        self.indices.get(normalize_ident(name).as_ref()).map(|&index| self.symbol(index))
+    }
+
+    /// Whether the symbol came from this table
+    pub fn contains(&self, symbol: Symbol) -> bool
+    {
+        symbol.table == self.id && symbol.index() < self.names.len()
+    }
+
+    /// Whether every symbol from the other table is valid in this one, like when this is a clone of it
+    pub fn includes(&self, other: &SymbolTable) -> bool
+    {
+        self.id == other.id && self.len() >= other.len()
+    }
+
+    /// The spelling the symbol was first interned with, if it came from this table
+    pub fn name(&self, symbol: Symbol) -> Option<&str>
+    {
+        if self.contains(symbol)
+        {
+            Some(&self.names[symbol.index()])
+        }
+        else
+        {
+            None
+        }
+    }
+
+    /// Every interned name, in the order of their symbols
+    pub fn names(&self) -> &[String]
+    {
+        &self.names
+    }
+
+    pub fn len(&self) -> usize
+    {
+        self.names.len()
+    }
+
+    pub fn is_empty(&self) -> bool
+    {
+        self.names.is_empty()
+    }
+
+    fn symbol(&self, index: u32) -> Symbol
+    {
+        Symbol {
+            table: self.id,
+            index
+        }
+    }
+}
+
+impl Default for SymbolTable
+{
+    fn default() -> SymbolTable
+    {
+        SymbolTable::new()
+    }
+}
+
+/// A variable name as it was written, along with its normalized form for looking it up.
+/// Locals get the symbol of their name when the program they're in is built.
+#[derive(Debug, Clone)]
+pub struct Ident
+{
+    pub name: String,
+    pub key: String,
+    pub symbol: Option<Symbol>
+}
+
+impl Ident
+{
+    pub fn new(name: &str) -> Ident
+    {
+        Ident {
+            name: String::from(name),
+            key: normalize_ident(name).into_owned(),
+            symbol: None
+        }
+    }
+
+    pub fn is_data_field(&self) -> bool
+    {
+        self.name.starts_with(':')
+    }
+}
+
+// Symbols depend on the program the ident was interned in, so only the names are compared
+impl PartialEq for Ident
+{
+    fn eq(&self, other: &Ident) -> bool
+    {
+        self.name == other.name
+    }
+}
+
+impl fmt::Display for Ident
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+    {
+        write!(f, "{}", self.name)
+    }
+}
+
+// Symbols only mean something within one program, so only the name is serialized
+impl Serialize for Ident
+{
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    {
+        self.name.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Ident
+{
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Ident, D::Error>
+    {
+        let name = String::deserialize(deserializer)?;
+        Ok(Ident::new(&name))
+    }
+}
@@ -0,0 +1,113 @@
+use std::fmt;
+use std::ops;
+
+use serde::{Serialize, Deserialize};
+
+/// A location in the source text. Lines and columns start at 1, the offset is in bytes from the start of the source.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct Span
+{
+    pub line: usize,
+    pub column: usize,
+    pub offset: usize,
+    pub length: usize
+}
+
+impl Span
+{
+    pub fn new(line: usize, column: usize, offset: usize, length: usize) -> Span
+    {
+        Span {
+            line,
+            column,
+            offset,
+            length
+        }
+    }
+
+    /// Returns the byte offset one past the end of the span
+    pub fn end(&self) -> usize
+    {
+        self.offset + self.length
+    }
+
+    /// Returns a span starting at this span and running until the end of the other span
+    pub fn to(self, other: Span) -> Span
+    {
+        let end = std::cmp::max(self.end(), other.end());
+
+        Span {
+            length: end - self.offset,
+            ..self
+        }
+    }
+}
+
+impl fmt::Display for Span
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+    {
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
+
+/// Wraps a value with the span of source it came from
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Spanned<T>
+{
+    pub node: T,
+    pub span: Span
+}
+
+impl<T> Spanned<T>
+{
+    pub fn new(node: T, span: Span) -> Spanned<T>
+    {
+        Spanned {
+            node,
+            span
+        }
+    }
+}
+
+impl<T> From<T> for Spanned<T>
+{
+    fn from(node: T) -> Spanned<T>
+    {
+        Spanned::new(node, Span::default())
+    }
+}
+
+impl<T> ops::Deref for Spanned<T>
+{
+    type Target = T;
+    fn deref(&self) -> &T
+    {
+        &self.node
+    }
+}
+
+impl<T> ops::DerefMut for Spanned<T>
+{
+    fn deref_mut(&mut self) -> &mut T
+    {
+        &mut self.node
+    }
+}
+
+// Spans only describe where a node came from, so they're ignored when comparing nodes
+impl<T: PartialEq> PartialEq for Spanned<T>
+{
+    fn eq(&self, other: &Self) -> bool
+    {
+        self.node == other.node
+    }
+}
+
+impl<T: fmt::Display> fmt::Display for Spanned<T>
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+    {
+        write!(f, "{}", self.node)
+    }
+}
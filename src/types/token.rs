@@ -1,8 +1,17 @@
 use std::fmt;
 
+use serde::{Serialize, Deserialize};
+
 use yolol_number::YololNumber;
 
-#[derive(Debug, PartialEq, Eq, Clone)]
+use crate::types::{
+    Span,
+    Spanned,
+    SlidingWindow,
+    VecWindow,
+};
+
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
 pub enum Token
 {
     Comment(String),
@@ -104,3 +113,98 @@ impl fmt::Display for Token
 
 
 
+
+/// A sliding window over tokens which also keeps track of where each token came from in the source
+pub struct TokenWindow
+{
+    tokens: VecWindow<Token>,
+    spans: Vec<Span>,
+}
+
+impl TokenWindow
+{
+    /// Gets the span of the token at the index relative to the window view
+    pub fn get_span(&self, index: usize) -> Option<Span>
+    {
+        self.spans.get(self.tokens.index() + index).cloned()
+    }
+
+    /// Gets the span of the token at the start of the window view.
+    /// If the window is exhausted, returns an empty span just past the last token.
+    pub fn current_span(&self) -> Span
+    {
+        match self.get_span(0)
+        {
+            Some(span) => span,
+            None => match self.spans.last()
+            {
+                Some(last) => Span::new(last.line, last.column + last.length, last.end(), 0),
+                None => Span::default()
+            }
+        }
+    }
+
+    /// Gets the span of the token just before the window view, if there is one
+    pub fn previous_span(&self) -> Option<Span>
+    {
+        match self.tokens.index()
+        {
+            0 => None,
+            index => self.spans.get(index - 1).cloned()
+        }
+    }
+}
+
+impl SlidingWindow for TokenWindow
+{
+    type Value = Token;
+
+    fn get_value(&self, index: usize) -> Option<&Self::Value>
+    {
+        self.tokens.get_value(index)
+    }
+
+    fn get_window(&self, view_size: usize) -> Option<&[Self::Value]>
+    {
+        self.tokens.get_window(view_size)
+    }
+
+    fn remaining_length(&self) -> usize
+    {
+        self.tokens.remaining_length()
+    }
+
+    fn move_view(&mut self, distance: isize) -> usize
+    {
+        self.tokens.move_view(distance)
+    }
+}
+
+impl From<Vec<Spanned<Token>>> for TokenWindow
+{
+    fn from(input: Vec<Spanned<Token>>) -> Self
+    {
+        let (tokens, spans): (Vec<Token>, Vec<Span>) = input.into_iter()
+            .map(|token| (token.node, token.span))
+            .unzip();
+
+        TokenWindow {
+            tokens: VecWindow::from(tokens),
+            spans
+        }
+    }
+}
+
+// Tokens built by hand don't have a source, so they all get empty spans
+impl From<Vec<Token>> for TokenWindow
+{
+    fn from(input: Vec<Token>) -> Self
+    {
+        let spans = vec![Span::default(); input.len()];
+
+        TokenWindow {
+            tokens: VecWindow::from(input),
+            spans
+        }
+    }
+}
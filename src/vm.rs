@@ -0,0 +1,336 @@
+use std::collections::HashMap;
+
+use crate::types::ast::{
+    operators::Operator as Op,
+    value::LiteralValue,
+    program::Program
+};
+use crate::types::EvaluationError;
+
+use crate::environment::{Environment, ContextMap, ErrorMode, normalize_ident};
+use crate::bytecode::{Bytecode, CompiledLine, Instruction};
+use crate::interpreter;
+
+/// Runs compiled bytecode with the same results as the tree walking interpreter, just faster.
+///
+/// The program's variables live in slots while it runs, so `env` only holds the rest of the state,
+/// like the next line and runtime errors. `sync_env` copies the slots back into it.
+/// Tracing isn't supported, use a `Chip` for that.
+#[derive(Debug, Clone)]
+pub struct Vm
+{
+    pub env: Environment,
+
+    bytecode: Bytecode,
+    slot_names: HashMap<String, usize>,
+    slots: Vec<LiteralValue>,
+    stack: Vec<LiteralValue>,
+    ticks: u64
+}
+
+impl Vm
+{
+    /// Compiles the program to run in the environment, taking the starting values of its variables from it
+    pub fn new(program: &Program, env: Environment) -> Vm
+    {
+        Vm::with_bytecode(Bytecode::compile(program), env)
+    }
+
+    pub fn with_bytecode(bytecode: Bytecode, env: Environment) -> Vm
+    {
+        let slots = bytecode.names.iter()
+            .map(|name| env.get_val(name))
+            .collect();
+
+        let slot_names = bytecode.names.iter()
+            .enumerate()
+            .map(|(slot, name)| (normalize_ident(name).into_owned(), slot))
+            .collect();
+
+        Vm {
+            env,
+            bytecode,
+            slot_names,
+            slots,
+            stack: Vec::new(),
+            ticks: 0
+        }
+    }
+
+    pub fn bytecode(&self) -> &Bytecode
+    {
+        &self.bytecode
+    }
+
+    /// Returns how many lines have been executed so far
+    pub fn ticks(&self) -> u64
+    {
+        self.ticks
+    }
+
+    /// Copies the value of every variable the program uses into the environment
+    pub fn sync_env(&mut self)
+    {
+        for (name, value) in self.bytecode.names.iter().zip(&self.slots)
+        {
+            self.env.set_val(name.clone(), value.clone());
+        }
+    }
+
+    /// Syncs the environment and hands it back
+    pub fn into_env(mut self) -> Environment
+    {
+        self.sync_env();
+        self.env
+    }
+
+    /// Executes the line at `env.next_line`, which counts as one tick.
+    /// Lines past the end of the program are treated as empty lines.
+    pub fn step(&mut self) -> Result<(), EvaluationError>
+    {
+        self.ticks += 1;
+        let line_number = interpreter::begin_line(&mut self.env);
+
+        let index = line_number - 1;
+        let line = if index >= 0
+        {
+            self.bytecode.lines.get(index as usize)
+        }
+        else
+        {
+            None
+        };
+
+        let line = match line
+        {
+            Some(line) => line,
+            None => return Ok(())
+        };
+
+        let mut statement = 0;
+        let result = execute(line, &mut self.env, &mut self.slots, &mut self.stack, &mut statement);
+        self.stack.clear();
+
+        match result
+        {
+            Ok(()) => Ok(()),
+            Err(error) => {
+                // Any errors that the game wouldn't recover from still get passed up
+                if self.env.error_mode == ErrorMode::AbortLine && error.is_runtime_error()
+                {
+                    self.env.record_runtime_error(line_number, statement, error);
+                    return Ok(());
+                }
+
+                Err(error)
+            }
+        }
+    }
+
+    /// Executes `count` ticks, stopping at the first error
+    pub fn run_ticks(&mut self, count: u64) -> Result<(), EvaluationError>
+    {
+        for _ in 0..count
+        {
+            self.step()?;
+        }
+
+        Ok(())
+    }
+}
+
+impl ContextMap for Vm
+{
+    fn get_val(&self, ident: &str) -> LiteralValue
+    {
+        match self.slot_names.get(normalize_ident(ident).as_ref())
+        {
+            Some(&slot) => self.slots[slot].clone(),
+            None => self.env.get_val(ident)
+        }
+    }
+
+    fn set_val(&mut self, ident: String, value: LiteralValue)
+    {
+        match self.slot_names.get(normalize_ident(&ident).as_ref())
+        {
+            Some(&slot) => self.slots[slot] = value,
+            None => self.env.set_val(ident, value)
+        }
+    }
+}
+
+// Runs a line's instructions, keeping track of which statement it's on for errors
+fn execute(line: &CompiledLine, env: &mut Environment, slots: &mut [LiteralValue], stack: &mut Vec<LiteralValue>, statement: &mut usize) -> Result<(), EvaluationError>
+{
+    let mut pc = 0;
+
+    while pc < line.code.len()
+    {
+        let span = line.spans[pc];
+        execute_instruction(line, env, slots, stack, statement, &mut pc)
+            .map_err(|error| error.with_span(span))?;
+    }
+
+    Ok(())
+}
+
+// Runs the instruction at `pc`, moving it on to the next instruction to run, or past the end of the line after a goto
+fn execute_instruction(line: &CompiledLine, env: &mut Environment, slots: &mut [LiteralValue], stack: &mut Vec<LiteralValue>, statement: &mut usize, pc: &mut usize) -> Result<(), EvaluationError>
+{
+    let instruction = &line.code[*pc];
+    *pc += 1;
+
+    match instruction
+    {
+        Instruction::Statement(index) => *statement = *index,
+
+        Instruction::Push(value) => stack.push(value.clone()),
+        Instruction::Pop => { pop(stack); },
+        Instruction::Load(slot) => stack.push(slots[*slot].clone()),
+        Instruction::Store(slot) => slots[*slot] = pop(stack),
+
+        Instruction::Binary(op) => {
+            let right = pop(stack);
+            let left = pop(stack);
+            stack.push(interpreter::apply_binary_op(*op, left, right)?);
+        },
+        Instruction::Unary(op) => {
+            let value = pop(stack);
+            stack.push(interpreter::apply_unary_op(*op, value)?);
+        },
+        Instruction::Assign(op) => {
+            let value = pop(stack);
+            let current_value = pop(stack);
+            stack.push(interpreter::apply_assign_op(*op, current_value, value)?);
+        },
+        Instruction::Step(op, slot) => {
+            let original_value = slots[*slot].clone();
+            let new_value = interpreter::apply_step_op(*op, original_value.clone())?;
+
+            slots[*slot] = new_value.clone();
+
+            stack.push(match op
+            {
+                Op::PreInc | Op::PreDec => new_value,
+                _ => original_value
+            });
+        },
+
+        Instruction::JumpIfFalse(target) => {
+            if pop(stack) == LiteralValue::get_false()
+            {
+                *pc = *target;
+            }
+        },
+        Instruction::Jump(target) => *pc = *target,
+        Instruction::Goto => {
+            env.next_line = interpreter::goto_line(env, pop(stack))?;
+            *pc = line.code.len();
+        },
+
+        Instruction::Fail(kind) => return Err(EvaluationError::new(kind.as_ref().clone()))
+    }
+
+    Ok(())
+}
+
+fn pop(stack: &mut Vec<LiteralValue>) -> LiteralValue
+{
+    stack.pop().expect("compiled code never pops more values than it pushed")
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    use std::collections::BTreeMap;
+
+    use crate::chip::Chip;
+
+    // Runs the source on a chip and on the vm, checking they end up with the same state and errors.
+    // Returns the chip's environment.
+    fn assert_equivalent(source: &str, error_mode: ErrorMode, ticks: u64) -> Environment
+    {
+        let mut chip = Chip::from_source("test", String::from(source)).unwrap();
+        chip.env.error_mode = error_mode;
+
+        let mut vm = Vm::new(&chip.program, chip.env.clone());
+
+        let chip_result = chip.run_ticks(ticks);
+        let vm_result = vm.run_ticks(ticks);
+        assert_eq!(chip_result, vm_result);
+
+        let vm_env = vm.into_env();
+        assert_eq!(chip.env.next_line, vm_env.next_line);
+
+        let errors = |env: &Environment| env.runtime_errors.iter()
+            .map(|error| (error.line, error.statement, error.error.clone()))
+            .collect::<Vec<_>>();
+        assert_eq!(errors(&chip.env), errors(&vm_env));
+
+        // The vm leaves unset variables that the program mentions at their default values
+        fn set<'a>(variables: impl Iterator<Item = (&'a str, &'a LiteralValue)>) -> BTreeMap<&'a str, LiteralValue>
+        {
+            variables
+                .filter(|(_, value)| **value != LiteralValue::get_false())
+                .map(|(name, value)| (name, value.clone()))
+                .collect()
+        }
+
+        assert_eq!(set(chip.env.local_context()), set(vm_env.local_context()));
+        assert_eq!(set(chip.env.global_context().iter()), set(vm_env.global_context().iter()));
+
+        chip.env
+    }
+
+    #[test]
+    fn fixtures_match_the_interpreter()
+    {
+        let fixtures = [
+            include_str!("../basicyolol.yolol"),
+            include_str!("../gross_test.yolol"),
+            include_str!("../parentest.yolol"),
+            include_str!("../problem_test.yolol"),
+            include_str!("../torturetest.yolol"),
+            include_str!("../xor_test.yolol"),
+            include_str!("../yololTest.yolol"),
+            include_str!("../yovec_test.yolol")
+        ];
+
+        for fixture in fixtures
+        {
+            assert_equivalent(fixture, ErrorMode::AbortLine, 500);
+        }
+    }
+
+    #[test]
+    fn runtime_errors_match_the_interpreter()
+    {
+        let source = "a = \"x\" b = -a c = 1\nd = 1 / 0 e = 1\nf = sqrt -1 g = 1\nh = a - 1 a++\ni = 1 goto a j = 1\n";
+
+        let env = assert_equivalent(source, ErrorMode::AbortLine, 25);
+        assert_eq!(env.runtime_errors.len(), 8);
+
+        assert_equivalent(source, ErrorMode::Propagate, 25);
+    }
+
+    #[test]
+    fn gotos_clamp_like_the_interpreter()
+    {
+        let source = "a++ goto 2.7\nb++ if b > 1 then goto -5 end goto 100\n";
+
+        // Lines 1, 2, 20, 1, 2, 1
+        let env = assert_equivalent(source, ErrorMode::AbortLine, 6);
+        assert_eq!(env.get_val("a"), LiteralValue::from(3));
+        assert_eq!(env.get_val("b"), LiteralValue::from(2));
+    }
+
+    #[test]
+    fn falling_off_the_end_matches_the_interpreter()
+    {
+        let env = assert_equivalent("a += 1\nb = a * 2\n", ErrorMode::AbortLine, 45);
+        assert_eq!(env.get_val("a"), LiteralValue::from(3));
+    }
+}